@@ -0,0 +1,173 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use crate::headermap::HeaderMap;
+
+/// A response body, either held in memory or streamed when the response is sent.
+///
+/// Streamed bodies are consumed by sending, so a response carrying one can
+/// only be written once.
+#[derive(Default)]
+pub enum Body {
+    #[default]
+    Empty,
+    Bytes(Vec<u8>),
+    /// Sent with a `Content-Length` taken from the file's metadata.
+    File(File),
+    /// With `len` the body is sent with a `Content-Length` and must produce
+    /// exactly that many bytes; without it the chunked coding is used, or
+    /// for an HTTP/1.0 peer the body runs until the connection closes.
+    Reader {
+        reader: Box<dyn Read + Send>,
+        len: Option<u64>,
+    },
+}
+
+impl Body {
+    pub fn from_reader(reader: impl Read + Send + 'static, len: Option<u64>) -> Self {
+        Body::Reader {
+            reader: Box::new(reader),
+            len,
+        }
+    }
+
+    /// The in-memory content, or `None` for a streamed body.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Body::Empty => Some(&[]),
+            Body::Bytes(bytes) => Some(bytes),
+            Body::File(_) | Body::Reader { .. } => None,
+        }
+    }
+
+    /// The length to announce in `Content-Length`, or `None` when it is only
+    /// known once the body has been read to the end.
+    pub fn len(&self) -> io::Result<Option<u64>> {
+        match self {
+            Body::Empty => Ok(Some(0)),
+            Body::Bytes(bytes) => Ok(Some(bytes.len() as u64)),
+            Body::File(file) => Ok(Some(file.metadata()?.len())),
+            Body::Reader { len, .. } => Ok(*len),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Body::Empty) || self.as_bytes().is_some_and(<[u8]>::is_empty)
+    }
+
+    // copies the body to `w`: exactly `len` bytes when it was announced,
+    // otherwise everything up to the end of the stream
+    pub(crate) fn copy_to<W: Write + ?Sized>(
+        &mut self,
+        w: &mut W,
+        len: Option<u64>,
+    ) -> io::Result<()> {
+        let reader: &mut dyn Read = match self {
+            Body::Empty => return Ok(()),
+            Body::Bytes(bytes) => return w.write_all(bytes),
+            Body::File(file) => file,
+            Body::Reader { reader, .. } => reader,
+        };
+        let Some(len) = len else {
+            return io::copy(reader, w).map(|_| ());
+        };
+        let copied = io::copy(&mut reader.take(len), w)?;
+        if copied < len {
+            // the head has promised `len` bytes; the connection cannot be reused
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("body ended after {} of {} bytes", copied, len),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Produces trailer fields for a chunked body from the data as it is sent,
+/// e.g. a checksum that is only known once the whole body has been read.
+pub trait TrailerSource: Send {
+    /// Called with each piece of the body in order.
+    fn observe(&mut self, data: &[u8]);
+
+    /// Called once after the last piece; the fields are sent after the body.
+    fn trailers(&mut self) -> HeaderMap;
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Empty => write!(f, "Empty"),
+            Body::Bytes(bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            Body::File(file) => f.debug_tuple("File").field(file).finish(),
+            Body::Reader { len, .. } => f.debug_struct("Reader").field("len", len).finish(),
+        }
+    }
+}
+
+/// In-memory bodies compare by content; streamed bodies never compare equal.
+impl PartialEq for Body {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.as_bytes(), other.as_bytes()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Body::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(bytes: &[u8]) -> Self {
+        Body::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<String> for Body {
+    fn from(text: String) -> Self {
+        Body::Bytes(text.into_bytes())
+    }
+}
+
+impl From<&str> for Body {
+    fn from(text: &str) -> Self {
+        Body::Bytes(text.as_bytes().to_vec())
+    }
+}
+
+impl From<File> for Body {
+    fn from(file: File) -> Self {
+        Body::File(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lengths() {
+        assert_eq!(Body::Empty.len().unwrap(), Some(0));
+        assert_eq!(Body::from("hello").len().unwrap(), Some(5));
+        assert_eq!(Body::from_reader(io::empty(), None).len().unwrap(), None);
+        assert!(Body::from(Vec::new()).is_empty());
+        assert_eq!(Body::from("a"), Body::from(vec![b'a']));
+        assert_ne!(Body::from_reader(io::empty(), Some(0)), Body::Empty);
+    }
+
+    #[test]
+    fn test_stream_must_match_its_length() {
+        let mut out = Vec::new();
+        let mut body = Body::from_reader(&b"abcdef"[..], Some(4));
+        body.copy_to(&mut out, Some(4)).unwrap();
+        assert_eq!(out, b"abcd");
+
+        let mut body = Body::from_reader(&b"ab"[..], Some(4));
+        let err = body.copy_to(&mut Vec::new(), Some(4)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
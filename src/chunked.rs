@@ -0,0 +1,476 @@
+use std::io::{self, BufRead, IoSlice, Read, Write};
+
+use crate::error::{ParseError, ReadError};
+use crate::headermap::{HeaderMap, parse_field_line};
+use crate::httprequest::is_token;
+use crate::httpresponse::write_all_vectored;
+use crate::parser::ParserConfig;
+
+/// A chunked body after removing the transfer coding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub body: Vec<u8>,
+    pub trailers: HeaderMap,
+    /// Input bytes used, including the final CRLF after the trailer section.
+    pub consumed: usize,
+}
+
+/// Decodes a complete chunked body (RFC 9112, section 7.1). `offset` is where
+/// `input` starts in the message and is only used for error positions.
+///
+/// The body may be any size, while the trailer section is held to the
+/// header limits of [`ParserConfig::default`].
+pub fn decode(input: &[u8], offset: usize) -> Result<Decoded, ParseError> {
+    decode_limited(input, offset, unlimited_body())
+}
+
+pub(crate) fn decode_limited(
+    input: &[u8],
+    offset: usize,
+    config: ParserConfig,
+) -> Result<Decoded, ParseError> {
+    let mut reader = ChunkedReader::with_limit(input, offset, config);
+    let mut body = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let n = reader.read_chunked(&mut buf).map_err(|err| match err {
+            ReadError::Parse(err) => err,
+            // a slice never fails to read, it only runs out
+            ReadError::Io(_) | ReadError::Closed => ParseError::TruncatedChunk {
+                offset: offset + input.len(),
+            },
+        })?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+
+    Ok(Decoded {
+        body,
+        trailers: reader.trailers,
+        consumed: reader.offset - offset,
+    })
+}
+
+/// Streams the decoded data of a chunked body out of `inner`, stopping right
+/// after the trailer section so the next message can be read from `inner`.
+#[derive(Debug)]
+pub struct ChunkedReader<R> {
+    inner: R,
+    state: State,
+    trailers: HeaderMap,
+    // position in the message, for error offsets
+    offset: usize,
+    // decoded bytes announced so far, which may not exceed `max_body`
+    total: usize,
+    // where the trailer section starts, once the last chunk has been read
+    trailer_start: usize,
+    config: ParserConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Size,
+    Data(usize),
+    DataEnd,
+    Trailers,
+    Done,
+}
+
+// the default limits, without one on the body
+fn unlimited_body() -> ParserConfig {
+    ParserConfig {
+        max_body: usize::MAX,
+        ..ParserConfig::default()
+    }
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    /// A reader for a body of any size whose lines and trailer section are
+    /// held to the limits of [`ParserConfig::default`].
+    pub fn new(inner: R) -> Self {
+        Self::with_limit(inner, 0, unlimited_body())
+    }
+
+    /// A reader held to the body and header limits of `config`, where the
+    /// trailer section counts as a header section of its own.
+    pub(crate) fn with_limit(inner: R, offset: usize, config: ParserConfig) -> Self {
+        Self {
+            inner,
+            state: State::Size,
+            trailers: HeaderMap::new(),
+            offset,
+            total: 0,
+            trailer_start: 0,
+            config,
+        }
+    }
+
+    /// The trailer fields, complete once the reader has returned end of body.
+    pub fn trailers(&self) -> &HeaderMap {
+        &self.trailers
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_chunked(&mut self, buf: &mut [u8]) -> Result<usize, ReadError> {
+        loop {
+            match self.state {
+                State::Size => {
+                    let line_start = self.offset;
+                    let line = self.read_line()?;
+                    match chunk_size(&line, line_start)? {
+                        0 => {
+                            self.state = State::Trailers;
+                            self.trailer_start = self.offset;
+                        }
+                        size => {
+                            // refuse the chunk before reading any of it
+                            self.total = self.total.saturating_add(size);
+                            if self.total > self.config.max_body {
+                                return Err(ParseError::BodyTooLarge { offset: line_start }.into());
+                            }
+                            self.state = State::Data(size);
+                        }
+                    }
+                }
+                State::Data(remaining) => {
+                    if buf.is_empty() {
+                        return Ok(0);
+                    }
+                    let available = self.inner.fill_buf()?;
+                    if available.is_empty() {
+                        return Err(ParseError::TruncatedChunk {
+                            offset: self.offset,
+                        }
+                        .into());
+                    }
+                    let n = available.len().min(remaining).min(buf.len());
+                    buf[..n].copy_from_slice(&available[..n]);
+                    self.consume(n);
+                    self.state = match remaining - n {
+                        0 => State::DataEnd,
+                        remaining => State::Data(remaining),
+                    };
+                    return Ok(n);
+                }
+                State::DataEnd => {
+                    let line_start = self.offset;
+                    if !self.read_line()?.is_empty() {
+                        return Err(ParseError::InvalidChunk { offset: line_start }.into());
+                    }
+                    self.state = State::Size;
+                }
+                State::Trailers => {
+                    self.read_trailer()?;
+                }
+                State::Done => return Ok(0),
+            }
+        }
+    }
+
+    // reads one trailer field, or the empty line that ends the body
+    fn read_trailer(&mut self) -> Result<(), ReadError> {
+        let line_start = self.offset;
+        let line = self.read_line()?;
+        // trailers are held to the same limits as the header section, so
+        // they cannot be used to get around them
+        if self.offset - self.trailer_start > self.config.max_header_bytes {
+            return Err(ParseError::HeadersTooLarge { offset: line_start }.into());
+        }
+        if line.is_empty() {
+            self.state = State::Done;
+            return Ok(());
+        }
+        if self.trailers.len() >= self.config.max_headers {
+            return Err(ParseError::TooManyHeaders { offset: line_start }.into());
+        }
+        let line = std::str::from_utf8(&line)
+            .map_err(|_| ParseError::BadHeaderLine { offset: line_start })?;
+        let (name, value) = parse_field_line(line, line_start)?;
+        self.trailers
+            .append(name, value)
+            .map_err(|_| ParseError::BadHeaderLine { offset: line_start })?;
+        Ok(())
+    }
+
+    // reads one line and returns it without its CRLF (or bare LF)
+    fn read_line(&mut self) -> Result<Vec<u8>, ReadError> {
+        let line_start = self.offset;
+        let mut line = Vec::new();
+        loop {
+            let available = self.inner.fill_buf()?;
+            if available.is_empty() {
+                return Err(ParseError::TruncatedChunk {
+                    offset: self.offset,
+                }
+                .into());
+            }
+            let (n, found) = match available.iter().position(|&b| b == b'\n') {
+                Some(end) => (end + 1, true),
+                None => (available.len(), false),
+            };
+            line.extend_from_slice(&available[..n]);
+            self.consume(n);
+            let content = line.strip_suffix(b"\n").unwrap_or(&line);
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            if content.len() > self.config.max_header_line {
+                let err = match self.state {
+                    State::Trailers => ParseError::HeaderTooLarge { offset: line_start },
+                    _ => ParseError::InvalidChunk { offset: line_start },
+                };
+                return Err(err.into());
+            }
+            if found {
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(line);
+            }
+        }
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.inner.consume(n);
+        self.offset += n;
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_chunked(buf).map_err(io::Error::from)
+    }
+}
+
+/// Chunk size used by [`ChunkedWriter::new`].
+pub const DEFAULT_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Applies the chunked transfer coding to everything written to it.
+///
+/// Small writes are gathered until a chunk is full, and `flush` sends what
+/// has been gathered as a shorter chunk. [`ChunkedWriter::finish`] must be
+/// called to end the body.
+#[derive(Debug)]
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    chunk_size: usize,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_chunk_size(inner, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(inner: W, chunk_size: usize) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    /// Writes the last chunk and an empty trailer section.
+    pub fn finish(self) -> io::Result<W> {
+        self.finish_with_trailers(&HeaderMap::new())
+    }
+
+    /// Writes the last chunk followed by `trailers`.
+    pub fn finish_with_trailers(mut self, trailers: &HeaderMap) -> io::Result<W> {
+        self.write_buffered()?;
+        let mut end = b"0\r\n".to_vec();
+        for (name, value) in trailers {
+            end.extend_from_slice(name.as_bytes());
+            end.extend_from_slice(b": ");
+            end.extend_from_slice(value.as_bytes());
+            end.extend_from_slice(b"\r\n");
+        }
+        end.extend_from_slice(b"\r\n");
+        self.inner.write_all(&end)?;
+        Ok(self.inner)
+    }
+
+    fn write_buffered(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            write_chunk(&mut self.inner, &self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+// `data` must not be empty, since an empty chunk ends the body
+fn write_chunk<W: Write>(inner: &mut W, data: &[u8]) -> io::Result<()> {
+    let size = format!("{:X}\r\n", data.len());
+    let mut bufs = [
+        IoSlice::new(size.as_bytes()),
+        IoSlice::new(data),
+        IoSlice::new(b"\r\n"),
+    ];
+    write_all_vectored(inner, &mut bufs)
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        // full chunks are written straight from the caller's buffer
+        if self.buf.is_empty() && data.len() >= self.chunk_size {
+            write_chunk(&mut self.inner, &data[..self.chunk_size])?;
+            return Ok(self.chunk_size);
+        }
+        let n = data.len().min(self.chunk_size - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == self.chunk_size {
+            self.write_buffered()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buffered()?;
+        self.inner.flush()
+    }
+}
+
+// chunk-size [ chunk-ext ], where extensions are `;name` or `;name=value`
+fn chunk_size(line: &[u8], offset: usize) -> Result<usize, ParseError> {
+    let invalid = ParseError::InvalidChunk { offset };
+    let line = std::str::from_utf8(line).map_err(|_| invalid.clone())?;
+    let (size, extensions) = match line.split_once(';') {
+        Some((size, extensions)) => (size, Some(extensions)),
+        None => (line, None),
+    };
+    let size = size.trim_end_matches([' ', '\t']);
+
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid);
+    }
+    let size = usize::from_str_radix(size, 16).map_err(|_| invalid.clone())?;
+
+    for extension in extensions.into_iter().flat_map(|e| e.split(';')) {
+        let mut parts = extension.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim_matches([' ', '\t']);
+        let value_ok = parts.next().is_none_or(|value| {
+            let value = value.trim_matches([' ', '\t']);
+            let quoted = value.len() >= 2 && value.starts_with('"') && value.ends_with('"');
+            is_token(value) || quoted
+        });
+        if !is_token(name) || !value_ok {
+            return Err(invalid);
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_chunks() {
+        let input = b"4\r\nWiki\r\n6\r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\n\r\nNEXT";
+        let decoded = decode(input, 0).unwrap();
+        assert_eq!(decoded.body, b"Wikipedia in \r\n\r\nchunks.");
+        assert!(decoded.trailers.is_empty());
+        assert_eq!(&input[decoded.consumed..], b"NEXT");
+    }
+
+    #[test]
+    fn test_decode_extensions_and_trailers() {
+        let input = b"5;name=value;flag\r\nhello\r\n0; last=\"yes\"\r\nDigest: sha-256=abc\r\nExpires: never\r\n\r\n";
+        let decoded = decode(input, 0).unwrap();
+        assert_eq!(decoded.body, b"hello");
+        assert_eq!(decoded.trailers.get("digest"), Some("sha-256=abc"));
+        assert_eq!(decoded.trailers.get("Expires"), Some("never"));
+        assert_eq!(decoded.consumed, input.len());
+    }
+
+    #[test]
+    fn test_reader_streams_chunks() {
+        let input: &[u8] = b"4\r\nWiki\r\n5\r\npedia\r\n0\r\nX-Sum: 9\r\n\r\nGET / HTTP/1.1\r\n";
+        // a one-byte buffer hands the decoder its input a byte at a time
+        let mut reader = ChunkedReader::new(io::BufReader::with_capacity(1, input));
+        let mut body = String::new();
+        reader.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "Wikipedia");
+        assert!(reader.is_done());
+        assert_eq!(reader.trailers().get("x-sum"), Some("9"));
+
+        let mut rest = String::new();
+        reader.into_inner().read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "GET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn test_reader_reports_truncation() {
+        let mut reader = ChunkedReader::new(&b"a\r\nhello"[..]);
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(
+            decode(b"zz\r\n", 10),
+            Err(ParseError::InvalidChunk { offset: 10 })
+        );
+        assert_eq!(
+            decode(b"5\r\nhelloX\r\n0\r\n\r\n", 0),
+            Err(ParseError::InvalidChunk { offset: 8 })
+        );
+        assert_eq!(
+            decode(b"5;bad name\r\nhello\r\n0\r\n\r\n", 0),
+            Err(ParseError::InvalidChunk { offset: 0 })
+        );
+        assert_eq!(
+            decode(b"ffffffffffffffffff\r\n", 0),
+            Err(ParseError::InvalidChunk { offset: 0 })
+        );
+        assert_eq!(
+            decode(b"a\r\nhello", 0),
+            Err(ParseError::TruncatedChunk { offset: 8 })
+        );
+        assert_eq!(
+            decode(b"0\r\n", 0),
+            Err(ParseError::TruncatedChunk { offset: 3 })
+        );
+    }
+
+    #[test]
+    fn test_writer_round_trips_through_decode() {
+        let mut writer = ChunkedWriter::with_chunk_size(Vec::new(), 256);
+        writer.write_all(b"Wiki").unwrap();
+        writer.write_all(b"").unwrap();
+        writer.write_all(&[b'x'; 300]).unwrap();
+        let mut trailers = HeaderMap::new();
+        trailers.append("X-Sum", "304").unwrap();
+        let encoded = writer.finish_with_trailers(&trailers).unwrap();
+        assert!(encoded.starts_with(b"100\r\nWiki"));
+        assert!(encoded.ends_with(b"\r\n30\r\nxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx\r\n0\r\nX-Sum: 304\r\n\r\n"));
+
+        let decoded = decode(&encoded, 0).unwrap();
+        assert_eq!(decoded.body.len(), 304);
+        assert_eq!(decoded.trailers.get("x-sum"), Some("304"));
+        assert_eq!(decoded.consumed, encoded.len());
+    }
+
+    #[test]
+    fn test_writer_flush_sends_a_short_chunk() {
+        let mut writer = ChunkedWriter::new(Vec::new());
+        writer.write_all(b"tick").unwrap();
+        writer.flush().unwrap();
+        writer.write_all(b"tock").unwrap();
+        let encoded = writer.finish().unwrap();
+        assert_eq!(encoded, b"4\r\ntick\r\n4\r\ntock\r\n0\r\n\r\n");
+    }
+}
@@ -0,0 +1,740 @@
+use std::io::{self, BufReader, Read};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::ClientError;
+use crate::headermap::HeaderMap;
+use crate::httprequest::{HttpRequest, Method, Resource};
+use crate::httpresponse::HttpResponse;
+use crate::parser::{ParserConfig, read_response_with};
+use crate::pool::{Pool, PoolStats, Pooled};
+use crate::statuscode::StatusCode;
+
+/// A blocking HTTP/1.1 client over plain TCP.
+///
+/// Connections are kept in a [`Pool`] and reused while the server allows it,
+/// and clones of a client share its pool. Redirects are followed by default,
+/// at most ten in a row, and there are no timeouts unless they are configured.
+#[derive(Debug, Clone)]
+pub struct Client {
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    follow_redirects: bool,
+    max_redirects: usize,
+    config: ParserConfig,
+    pool: Arc<Pool>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self {
+            connect_timeout: None,
+            timeout: None,
+            follow_redirects: true,
+            max_redirects: 10,
+            config: ParserConfig::default(),
+            pool: Arc::default(),
+        }
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Longest wait for a connection to be established.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Longest wait for any single read or write once connected, so a server
+    /// that stops responding fails the request with [`ClientError::Timeout`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Whether redirects are followed or returned as they are.
+    pub fn follow_redirects(mut self, follow: bool) -> Self {
+        self.follow_redirects = follow;
+        self
+    }
+
+    /// Most redirects followed for one request before giving up with
+    /// [`ClientError::TooManyRedirects`].
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Limits applied to the responses the client reads.
+    pub fn parser_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Keeps connections in `pool`, which may be shared with other clients.
+    pub fn pool(mut self, pool: Arc<Pool>) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
+    /// Fetches an absolute `http` URL.
+    pub fn get(&self, url: &str) -> Result<HttpResponse, ClientError> {
+        self.send(request(Method::Get, url, Vec::new())?)
+    }
+
+    pub fn post(&self, url: &str, body: impl Into<Vec<u8>>) -> Result<HttpResponse, ClientError> {
+        self.send(request(Method::Post, url, body.into())?)
+    }
+
+    /// Sends `request` and reads the final response, skipping interim 1xx
+    /// responses and following redirects.
+    ///
+    /// The request names its server with an absolute-form target or, for an
+    /// origin-form target, with its `Host` header; either way it goes out in
+    /// origin-form. A 303, or a 301 or 302 to a POST, is followed with a GET
+    /// without a body as browsers do, while 307 and 308 repeat the request
+    /// unchanged (RFC 9110, section 15.4). Credentials are not passed on to
+    /// another host.
+    pub fn send(&self, request: HttpRequest) -> Result<HttpResponse, ClientError> {
+        let mut request = absolute(request)?;
+        let mut redirects = 0;
+        loop {
+            let response = self.send_once(&request)?;
+            let location = match response.headers().get("Location") {
+                Some(location) if self.follow_redirects && is_redirect(response.status()) => {
+                    location
+                }
+                _ => return Ok(response),
+            };
+            if redirects == self.max_redirects {
+                return Err(ClientError::TooManyRedirects {
+                    limit: self.max_redirects,
+                });
+            }
+            redirects += 1;
+            request = redirect(request, response.status(), location)?;
+        }
+    }
+
+    fn send_once(&self, request: &HttpRequest) -> Result<HttpResponse, ClientError> {
+        let Resource::Absolute {
+            authority,
+            path,
+            query,
+            ..
+        } = &request.resource
+        else {
+            return Err(ClientError::InvalidUrl(request.resource.to_string()));
+        };
+        let host = host(authority);
+
+        // servers must accept absolute-form, but only proxies are sent it
+        // (RFC 9112, section 3.2.2)
+        let mut wire = request.clone();
+        wire.resource = Resource::Path {
+            path: if path.is_empty() { "/" } else { path }.to_string(),
+            query: query.clone(),
+        };
+        wire.headers
+            .insert("Host", host)
+            .map_err(|_| ClientError::InvalidUrl(request.resource.to_string()))?;
+
+        let conn = self.pool.checkout(host, || self.connect(host))?;
+        match self.exchange(conn, &wire) {
+            // the server may have closed an idle connection just as the request
+            // went out; only a request that is safe to repeat is sent again
+            // (RFC 9112, section 9.3.1)
+            Err((err, true)) if is_stale(&err) && wire.method.is_idempotent() => {
+                let conn = self.pool.checkout(host, || self.connect(host))?;
+                self.exchange(conn, &wire).map_err(|(err, _)| err)
+            }
+            result => result.map_err(|(err, _)| err),
+        }
+    }
+
+    // Sends `request` on `conn` and reads the final response, returning the
+    // connection to the pool if both sides allow it. An error comes with
+    // whether the connection had been reused.
+    fn exchange(
+        &self,
+        conn: Pooled<'_>,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, (ClientError, bool)> {
+        let reused = conn.reused();
+        let fail = |err: ClientError| (err, reused);
+        let stream = conn.stream();
+        // a pooled connection may have been opened by a client with other timeouts
+        stream
+            .set_read_timeout(self.timeout)
+            .map_err(|e| fail(e.into()))?;
+        stream
+            .set_write_timeout(self.timeout)
+            .map_err(|e| fail(e.into()))?;
+        request
+            .write_to(&mut &*stream)
+            .map_err(|e| fail(e.into()))?;
+
+        let mut reader = BufReader::new(stream);
+        let (response, close_delimited) = loop {
+            let (mut response, mut body) =
+                read_response_with(&mut reader, &request.method, self.config)
+                    .map_err(|e| fail(e.into()))?;
+            // interim responses precede the real one, unless the protocol switches
+            let status = response.status();
+            if status.is_informational() && status != StatusCode::SWITCHING_PROTOCOLS {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            body.read_to_end(&mut bytes).map_err(|e| fail(e.into()))?;
+            if let Some(trailers) = body.trailers() {
+                response.set_received_trailers(trailers.clone());
+            }
+            response.set_body(bytes);
+            break (response, body.is_close_delimited());
+        };
+
+        let status = response.status();
+        let reusable = !close_delimited
+            // anything beyond the response would be mistaken for the next one
+            && reader.buffer().is_empty()
+            && request.keep_alive()
+            && response.keep_alive()
+            && status != StatusCode::SWITCHING_PROTOCOLS
+            && !(request.method == Method::Connect && status.is_success());
+        if reusable {
+            conn.keep(response.headers());
+        }
+        Ok(response)
+    }
+
+    fn connect(&self, host: &str) -> Result<TcpStream, ClientError> {
+        let addr = if has_port(host) {
+            host.to_string()
+        } else {
+            format!("{}:80", host.trim_end_matches(':'))
+        };
+        let mut last_err = None;
+        for addr in addr.to_socket_addrs().map_err(ClientError::connect)? {
+            let attempt = match self.connect_timeout {
+                Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+            match attempt {
+                Ok(stream) => {
+                    stream.set_read_timeout(self.timeout)?;
+                    stream.set_write_timeout(self.timeout)?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        let err = last_err.unwrap_or_else(|| io::Error::from(io::ErrorKind::NotFound));
+        Err(ClientError::connect(err))
+    }
+}
+
+fn request(method: Method, url: &str, body: Vec<u8>) -> Result<HttpRequest, ClientError> {
+    let resource = url
+        .parse()
+        .map_err(|_| ClientError::InvalidUrl(url.to_string()))?;
+    let mut request = HttpRequest::new(method, resource);
+    request.msg_body = body;
+    Ok(request)
+}
+
+// Gives `request` an absolute-form `http` target, taking the authority of
+// an origin-form target from its `Host` header.
+fn absolute(mut request: HttpRequest) -> Result<HttpRequest, ClientError> {
+    if let Resource::Path { path, query } = &request.resource {
+        let Some(authority) = request.headers.get("Host").filter(|h| !h.is_empty()) else {
+            return Err(ClientError::InvalidUrl(request.resource.to_string()));
+        };
+        request.resource = Resource::Absolute {
+            scheme: "http".to_string(),
+            authority: authority.to_string(),
+            path: path.clone(),
+            query: query.clone(),
+        };
+    }
+    match &request.resource {
+        Resource::Absolute { scheme, .. } if scheme != "http" => {
+            Err(ClientError::UnsupportedScheme(scheme.clone()))
+        }
+        Resource::Absolute { .. } => Ok(request),
+        other => Err(ClientError::InvalidUrl(other.to_string())),
+    }
+}
+
+// errors that mean the connection was closed before the request was read
+fn is_stale(err: &ClientError) -> bool {
+    match err {
+        ClientError::Closed => true,
+        ClientError::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+        ),
+        _ => false,
+    }
+}
+
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+// The request to send after being redirected to `location` with `status`.
+fn redirect(
+    mut request: HttpRequest,
+    status: StatusCode,
+    location: &str,
+) -> Result<HttpRequest, ClientError> {
+    let target = resolve(&request.resource, location)
+        .ok_or_else(|| ClientError::InvalidRedirect(location.to_string()))?;
+    let becomes_get = match status {
+        StatusCode::SEE_OTHER => request.method != Method::Head,
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => request.method == Method::Post,
+        _ => false,
+    };
+    if becomes_get {
+        request.method = Method::Get;
+        request.msg_body.clear();
+        request.trailers = HeaderMap::new();
+        for name in ["Content-Type", "Content-Encoding", "Content-Language"] {
+            request.headers.remove(name);
+        }
+    }
+    if target.authority() != request.resource.authority() {
+        for name in ["Authorization", "Proxy-Authorization", "Cookie"] {
+            request.headers.remove(name);
+        }
+    }
+    request.resource = target;
+    absolute(request)
+}
+
+// Resolves a `Location` value against the URL it answered (RFC 3986,
+// section 5.2), leaving any dot segments for the server to interpret.
+fn resolve(base: &Resource, location: &str) -> Option<Resource> {
+    let Resource::Absolute {
+        scheme,
+        authority,
+        path,
+        query,
+    } = base
+    else {
+        return None;
+    };
+    let location = location.split('#').next().unwrap_or("");
+    let url = if has_scheme(location) {
+        location.to_string()
+    } else if location.starts_with("//") {
+        format!("{}:{}", scheme, location)
+    } else if location.starts_with('/') {
+        format!("{}://{}{}", scheme, authority, location)
+    } else if location.is_empty() {
+        let query = query.as_ref().map_or(String::new(), |q| format!("?{}", q));
+        format!("{}://{}{}{}", scheme, authority, path, query)
+    } else if location.starts_with('?') {
+        format!("{}://{}{}{}", scheme, authority, path, location)
+    } else {
+        let dir = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+        let dir = if dir.is_empty() { "/" } else { dir };
+        format!("{}://{}{}{}", scheme, authority, dir, location)
+    };
+    match url.parse() {
+        Ok(resource @ Resource::Absolute { .. }) => Some(resource),
+        _ => None,
+    }
+}
+
+fn has_scheme(s: &str) -> bool {
+    s.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'))
+    })
+}
+
+// the host and port of an authority, without any userinfo
+fn host(authority: &str) -> &str {
+    authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host)
+}
+
+// whether `host` ends in a port, telling one apart from the colons of an
+// IPv6 literal such as `[::1]`
+fn has_port(host: &str) -> bool {
+    host.rsplit_once(':').is_some_and(|(name, port)| {
+        !port.is_empty()
+            && port.bytes().all(|b| b.is_ascii_digit())
+            && (!name.contains(':') || name.ends_with(']'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseError;
+    use crate::parser::read_request;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    // Answers one connection per response, in order, and hands back the
+    // requests it received; the responses say `Connection: close` so that the
+    // client does not try to reuse them.
+    fn serve(
+        listener: TcpListener,
+        responses: Vec<Vec<u8>>,
+    ) -> thread::JoinHandle<Vec<HttpRequest>> {
+        thread::spawn(move || {
+            let mut seen = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let (mut req, mut body) = read_request(BufReader::new(&stream)).unwrap();
+                body.read_to_end(&mut req.msg_body).unwrap();
+                stream.write_all(&response).unwrap();
+                seen.push(req);
+            }
+            seen
+        })
+    }
+
+    fn listen() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        (listener, base)
+    }
+
+    #[test]
+    fn test_get_sends_origin_form_with_host() {
+        let (listener, base) = listen();
+        let server = serve(
+            listener,
+            vec![b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 5\r\nX-Test: 1\r\n\r\nhello".to_vec()],
+        );
+
+        let response = Client::new().get(&format!("{}/a?b=1", base)).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("x-test"), Some("1"));
+        assert_eq!(response.body().as_bytes(), Some(&b"hello"[..]));
+
+        let seen = server.join().unwrap();
+        assert_eq!(seen[0].method, Method::Get);
+        assert_eq!(seen[0].resource, "/a?b=1".parse::<Resource>().unwrap());
+        assert_eq!(seen[0].headers.get("Host"), Some(&base["http://".len()..]));
+        // the connection is left open for reuse
+        assert!(!seen[0].headers.contains_key("Connection"));
+    }
+
+    #[test]
+    fn test_interim_chunked_and_close_delimited_responses() {
+        let (listener, base) = listen();
+        let server = serve(
+            listener,
+            vec![
+                b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nConnection: close\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\nX-Sum: 7\r\n\r\n".to_vec(),
+                b"HTTP/1.0 200 OK\r\n\r\nuntil the connection closes".to_vec(),
+            ],
+        );
+
+        let client = Client::new();
+        let response = client.post(&format!("{}/up", base), "data").unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body().as_bytes(), Some(&b"ok"[..]));
+        assert_eq!(response.trailers().unwrap().get("x-sum"), Some("7"));
+
+        let response = client.get(&base).unwrap();
+        assert_eq!(
+            response.body().as_bytes(),
+            Some(&b"until the connection closes"[..])
+        );
+
+        let seen = server.join().unwrap();
+        assert_eq!(seen[0].msg_body, b"data");
+        assert_eq!(seen[1].resource.path(), "/");
+    }
+
+    #[test]
+    fn test_redirects_rewrite_methods() {
+        let cases = [
+            (Method::Post, StatusCode::MOVED_PERMANENTLY, Method::Get),
+            (Method::Post, StatusCode::FOUND, Method::Get),
+            (Method::Put, StatusCode::FOUND, Method::Put),
+            (Method::Post, StatusCode::SEE_OTHER, Method::Get),
+            (Method::Put, StatusCode::SEE_OTHER, Method::Get),
+            (Method::Head, StatusCode::SEE_OTHER, Method::Head),
+            (Method::Post, StatusCode::TEMPORARY_REDIRECT, Method::Post),
+            (Method::Post, StatusCode::PERMANENT_REDIRECT, Method::Post),
+        ];
+        for (method, status, expected) in cases {
+            let (listener, base) = listen();
+            let redirect = format!(
+                "HTTP/1.1 {}\r\nLocation: next?page=2\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+                status
+            );
+            let server = serve(
+                listener,
+                vec![
+                    redirect.into_bytes(),
+                    b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n".to_vec(),
+                ],
+            );
+
+            let mut req = request(
+                method.clone(),
+                &format!("{}/dir/form", base),
+                b"data".to_vec(),
+            )
+            .unwrap();
+            req.headers.insert("Content-Type", "text/plain").unwrap();
+            let response = Client::new().send(req).unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let seen = server.join().unwrap();
+            let followed = &seen[1];
+            assert_eq!(followed.method, expected, "{} after {}", method, status);
+            assert_eq!(followed.resource.path(), "/dir/next");
+            assert_eq!(followed.resource.query(), Some("page=2"));
+            if expected == method {
+                assert_eq!(followed.msg_body, b"data");
+                assert_eq!(followed.headers.get("Content-Type"), Some("text/plain"));
+            } else {
+                assert!(followed.msg_body.is_empty());
+                assert!(!followed.headers.contains_key("Content-Type"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_redirect_limits() {
+        let again = b"HTTP/1.1 302 Found\r\nLocation: /again\r\nConnection: close\r\nContent-Length: 0\r\n\r\n".to_vec();
+        let (listener, base) = listen();
+        let server = serve(listener, vec![again.clone(); 3]);
+        let err = Client::new().max_redirects(2).get(&base).unwrap_err();
+        assert!(matches!(err, ClientError::TooManyRedirects { limit: 2 }));
+        assert_eq!(server.join().unwrap().len(), 3);
+
+        let (listener, base) = listen();
+        let server = serve(listener, vec![again]);
+        let response = Client::new().follow_redirects(false).get(&base).unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers().get("Location"), Some("/again"));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_cross_host_redirect_drops_credentials() {
+        let (first, first_base) = listen();
+        let (second, second_base) = listen();
+        let redirect = format!(
+            "HTTP/1.1 308 Permanent Redirect\r\nLocation: {}/moved#frag\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+            second_base
+        );
+        let first = serve(first, vec![redirect.into_bytes()]);
+        let second = serve(
+            second,
+            vec![b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n".to_vec()],
+        );
+
+        // an origin-form request names its server with Host
+        let mut req = request(Method::Get, "/start", Vec::new()).unwrap();
+        req.headers
+            .insert("Host", &first_base["http://".len()..])
+            .unwrap();
+        req.headers
+            .insert("Authorization", "Bearer secret")
+            .unwrap();
+        req.headers.insert("Accept", "*/*").unwrap();
+        Client::new().send(req).unwrap();
+
+        let seen = first.join().unwrap();
+        assert_eq!(seen[0].headers.get("Authorization"), Some("Bearer secret"));
+        let seen = second.join().unwrap();
+        assert_eq!(seen[0].resource.path(), "/moved");
+        assert_eq!(
+            seen[0].headers.get("Host"),
+            Some(&second_base["http://".len()..])
+        );
+        assert_eq!(seen[0].headers.get("Accept"), Some("*/*"));
+        assert!(!seen[0].headers.contains_key("Authorization"));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (listener, base) = listen();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            thread::sleep(Duration::from_millis(500));
+            drop(stream);
+        });
+        let err = Client::new()
+            .timeout(Duration::from_millis(50))
+            .get(&base)
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Timeout), "{:?}", err);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let (listener, base) = listen();
+        drop(listener);
+        let err = Client::new().get(&base).unwrap_err();
+        assert!(matches!(err, ClientError::Connect(_)), "{:?}", err);
+
+        let err = Client::new().get("https://example.com/").unwrap_err();
+        assert!(matches!(err, ClientError::UnsupportedScheme(scheme) if scheme == "https"));
+        let err = Client::new().get("/no/host").unwrap_err();
+        assert!(matches!(err, ClientError::InvalidUrl(_)));
+
+        let (listener, base) = listen();
+        let server = serve(listener, vec![b"garbage\r\n\r\n".to_vec(), Vec::new()]);
+        let err = Client::new().get(&base).unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::Parse(ParseError::MalformedStatusLine { offset: 0 })
+        ));
+        let err = Client::new().get(&base).unwrap_err();
+        assert!(matches!(err, ClientError::Closed), "{:?}", err);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_resolve_locations() {
+        let base: Resource = "http://a.example/b/c?q".parse().unwrap();
+        let cases = [
+            ("http://other.example/x", "http://other.example/x"),
+            ("//other.example:81/x", "http://other.example:81/x"),
+            ("/x?y=1", "http://a.example/x?y=1"),
+            ("d", "http://a.example/b/d"),
+            ("?r", "http://a.example/b/c?r"),
+            ("", "http://a.example/b/c?q"),
+            ("/x#section", "http://a.example/x"),
+        ];
+        for (location, expected) in cases {
+            let resolved = resolve(&base, location).unwrap();
+            assert_eq!(resolved.to_string(), expected, "{:?}", location);
+        }
+        assert!(resolve(&base, "/has space").is_none());
+        assert!(resolve(&base, "mailto:someone@example.com").is_none());
+
+        assert!(has_port("example.com:8080"));
+        assert!(has_port("[::1]:80"));
+        assert!(!has_port("[::1]"));
+        assert!(!has_port("example.com"));
+        assert_eq!(host("user:pw@example.com:81"), "example.com:81");
+    }
+
+    // A keep-alive server: each connection is answered until it closes, or
+    // until `answer` returns `None`, which drops it without a response.
+    fn serve_keep_alive(
+        listener: TcpListener,
+        requests: usize,
+        answer: fn(&HttpRequest) -> Option<&'static [u8]>,
+    ) -> thread::JoinHandle<usize> {
+        thread::spawn(move || {
+            let (mut handled, mut accepted) = (0, 0);
+            while handled < requests {
+                let (mut stream, _) = listener.accept().unwrap();
+                accepted += 1;
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                while handled < requests {
+                    let Ok((req, _)) = read_request(&mut reader) else {
+                        break;
+                    };
+                    handled += 1;
+                    match answer(&req) {
+                        Some(response) => stream.write_all(response).unwrap(),
+                        None => break,
+                    }
+                    if req.resource.path() == "/close" {
+                        break;
+                    }
+                }
+            }
+            accepted
+        })
+    }
+
+    #[test]
+    fn test_connections_are_reused() {
+        let (listener, base) = listen();
+        let server = serve_keep_alive(listener, 4, |req| match req.resource.path() {
+            "/close" => {
+                Some(b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok")
+            }
+            _ => Some(b"HTTP/1.1 200 OK\r\nKeep-Alive: timeout=30\r\nContent-Length: 2\r\n\r\nok"),
+        });
+
+        let client = Client::new();
+        for path in ["/a", "/b", "/close", "/c"] {
+            let response = client.clone().get(&format!("{}{}", base, path)).unwrap();
+            assert_eq!(response.body().as_bytes(), Some(&b"ok"[..]));
+        }
+        assert_eq!(server.join().unwrap(), 2);
+        assert_eq!(
+            client.pool_stats(),
+            PoolStats {
+                active: 0,
+                idle: 1,
+                opened: 2,
+                reused: 2,
+                evicted: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_request_on_a_connection_closed_by_the_server_is_retried() {
+        // the second request on the first connection gets no answer
+        static SEEN: AtomicUsize = AtomicUsize::new(0);
+        let (listener, base) = listen();
+        let server = serve_keep_alive(listener, 3, |_| match SEEN.fetch_add(1, Ordering::SeqCst) {
+            1 => None,
+            _ => Some(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"),
+        });
+
+        let client = Client::new();
+        client.get(&base).unwrap();
+        client.get(&base).unwrap();
+        assert_eq!(server.join().unwrap(), 2);
+        let stats = client.pool_stats();
+        assert_eq!((stats.opened, stats.reused), (2, 1));
+    }
+
+    #[test]
+    fn test_unsafe_requests_are_not_retried() {
+        let (listener, base) = listen();
+        let server = serve_keep_alive(listener, 2, |req| match req.method {
+            Method::Post => None,
+            _ => Some(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"),
+        });
+
+        let client = Client::new();
+        client.get(&base).unwrap();
+        let err = client.post(&base, "once only").unwrap_err();
+        assert!(matches!(err, ClientError::Closed), "{:?}", err);
+        assert_eq!(server.join().unwrap(), 1);
+    }
+}
@@ -0,0 +1,426 @@
+use std::fmt;
+use std::io;
+
+use crate::statuscode::StatusCode;
+
+/// Reasons a request or response could not be parsed. Offsets are byte
+/// positions into the raw input so a 400 response can point at the exact spot
+/// that was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    MalformedRequestLine {
+        offset: usize,
+    },
+    InvalidMethod {
+        offset: usize,
+    },
+    InvalidTarget {
+        offset: usize,
+    },
+    InvalidPercentEncoding {
+        offset: usize,
+    },
+    /// A `%2F` in the path, which would otherwise smuggle a separator past routing.
+    EncodedSlash {
+        offset: usize,
+    },
+    EncodedNul {
+        offset: usize,
+    },
+    UnsupportedVersion {
+        offset: usize,
+    },
+    /// The request line is longer than [`ParserConfig::max_request_line`].
+    ///
+    /// [`ParserConfig::max_request_line`]: crate::parser::ParserConfig::max_request_line
+    RequestLineTooLong {
+        offset: usize,
+    },
+    /// A response's status line is not `HTTP-version SP status-code SP reason`.
+    MalformedStatusLine {
+        offset: usize,
+    },
+    /// A response's status line is longer than [`ParserConfig::max_request_line`].
+    ///
+    /// [`ParserConfig::max_request_line`]: crate::parser::ParserConfig::max_request_line
+    StatusLineTooLong {
+        offset: usize,
+    },
+    BadHeaderLine {
+        offset: usize,
+    },
+    /// A single header line is longer than the configured limit.
+    HeaderTooLarge {
+        offset: usize,
+    },
+    /// The header section as a whole is larger than the configured limit.
+    HeadersTooLarge {
+        offset: usize,
+    },
+    TooManyHeaders {
+        offset: usize,
+    },
+    /// The body is, or declares itself, larger than the configured limit.
+    BodyTooLarge {
+        offset: usize,
+    },
+    InvalidContentLength {
+        offset: usize,
+    },
+    /// A transfer coding other than a single, final `chunked`.
+    InvalidTransferEncoding {
+        offset: usize,
+    },
+    InvalidChunk {
+        offset: usize,
+    },
+    /// The input ended inside a chunked body.
+    TruncatedChunk {
+        offset: usize,
+    },
+    /// The input ended before the empty line that terminates the head.
+    IncompleteHead {
+        offset: usize,
+    },
+    TruncatedBody {
+        expected: usize,
+        received: usize,
+    },
+    /// The input is an HTTP/2 connection preface rather than an HTTP/1.x request.
+    Http2Preface,
+    /// A resumable parser was handed less input than an earlier call, so the
+    /// buffer no longer starts with the bytes it has already parsed.
+    ShortenedInput {
+        offset: usize,
+    },
+}
+
+impl ParseError {
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            ParseError::MalformedRequestLine { offset }
+            | ParseError::InvalidMethod { offset }
+            | ParseError::InvalidTarget { offset }
+            | ParseError::InvalidPercentEncoding { offset }
+            | ParseError::EncodedSlash { offset }
+            | ParseError::EncodedNul { offset }
+            | ParseError::UnsupportedVersion { offset }
+            | ParseError::RequestLineTooLong { offset }
+            | ParseError::MalformedStatusLine { offset }
+            | ParseError::StatusLineTooLong { offset }
+            | ParseError::BadHeaderLine { offset }
+            | ParseError::HeaderTooLarge { offset }
+            | ParseError::HeadersTooLarge { offset }
+            | ParseError::TooManyHeaders { offset }
+            | ParseError::BodyTooLarge { offset }
+            | ParseError::InvalidContentLength { offset }
+            | ParseError::InvalidTransferEncoding { offset }
+            | ParseError::InvalidChunk { offset }
+            | ParseError::TruncatedChunk { offset }
+            | ParseError::IncompleteHead { offset }
+            | ParseError::ShortenedInput { offset } => Some(*offset),
+            ParseError::TruncatedBody { .. } | ParseError::Http2Preface => None,
+        }
+    }
+
+    /// The status of the response that should reject the request.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ParseError::RequestLineTooLong { .. } => StatusCode::URI_TOO_LONG,
+            ParseError::HeaderTooLarge { .. }
+            | ParseError::HeadersTooLarge { .. }
+            | ParseError::TooManyHeaders { .. } => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            ParseError::BodyTooLarge { .. } => StatusCode::CONTENT_TOO_LARGE,
+            ParseError::UnsupportedVersion { .. } | ParseError::Http2Preface => {
+                StatusCode::HTTP_VERSION_NOT_SUPPORTED
+            }
+            // the peer did nothing wrong
+            ParseError::ShortenedInput { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MalformedRequestLine { offset } => {
+                write!(f, "malformed request line at byte {}", offset)
+            }
+            ParseError::InvalidMethod { offset } => write!(f, "invalid method at byte {}", offset),
+            ParseError::InvalidTarget { offset } => {
+                write!(f, "invalid request target at byte {}", offset)
+            }
+            ParseError::InvalidPercentEncoding { offset } => {
+                write!(f, "invalid percent-encoding at byte {}", offset)
+            }
+            ParseError::EncodedSlash { offset } => {
+                write!(f, "encoded slash in path at byte {}", offset)
+            }
+            ParseError::EncodedNul { offset } => {
+                write!(f, "encoded NUL byte in path at byte {}", offset)
+            }
+            ParseError::UnsupportedVersion { offset } => {
+                write!(f, "unsupported HTTP version at byte {}", offset)
+            }
+            ParseError::RequestLineTooLong { offset } => {
+                write!(f, "request line too long at byte {}", offset)
+            }
+            ParseError::MalformedStatusLine { offset } => {
+                write!(f, "malformed status line at byte {}", offset)
+            }
+            ParseError::StatusLineTooLong { offset } => {
+                write!(f, "status line too long at byte {}", offset)
+            }
+            ParseError::BadHeaderLine { offset } => {
+                write!(f, "malformed header line at byte {}", offset)
+            }
+            ParseError::HeaderTooLarge { offset } => {
+                write!(f, "header line too large at byte {}", offset)
+            }
+            ParseError::HeadersTooLarge { offset } => {
+                write!(f, "header section too large at byte {}", offset)
+            }
+            ParseError::TooManyHeaders { offset } => {
+                write!(f, "too many header fields at byte {}", offset)
+            }
+            ParseError::BodyTooLarge { offset } => write!(f, "body too large at byte {}", offset),
+            ParseError::InvalidContentLength { offset } => {
+                write!(f, "invalid Content-Length at byte {}", offset)
+            }
+            ParseError::InvalidTransferEncoding { offset } => {
+                write!(f, "unsupported Transfer-Encoding at byte {}", offset)
+            }
+            ParseError::InvalidChunk { offset } => {
+                write!(f, "malformed chunk at byte {}", offset)
+            }
+            ParseError::TruncatedChunk { offset } => {
+                write!(f, "chunked body ended unexpectedly at byte {}", offset)
+            }
+            ParseError::IncompleteHead { offset } => {
+                write!(f, "message head ended unexpectedly at byte {}", offset)
+            }
+            ParseError::TruncatedBody { expected, received } => write!(
+                f,
+                "body truncated: expected {} bytes, received {}",
+                expected, received
+            ),
+            ParseError::Http2Preface => write!(f, "unexpected HTTP/2 connection preface"),
+            ParseError::ShortenedInput { offset } => {
+                write!(f, "input shorter than the {} bytes already parsed", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A header name that is not a token, or a value containing control characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidHeader {
+    Name(String),
+    Value(String),
+    /// A status line reason phrase with control characters such as CR or LF.
+    Reason(String),
+}
+
+impl fmt::Display for InvalidHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidHeader::Name(name) => write!(f, "invalid header name {:?}", name),
+            InvalidHeader::Value(value) => write!(f, "invalid header value {:?}", value),
+            InvalidHeader::Reason(reason) => write!(f, "invalid reason phrase {:?}", reason),
+        }
+    }
+}
+
+impl std::error::Error for InvalidHeader {}
+
+/// A status code outside 100..=599, or text that is not three digits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidStatusCode(pub String);
+
+impl fmt::Display for InvalidStatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid status code {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidStatusCode {}
+
+/// Why a body could not be read as text in its declared charset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextError {
+    InvalidUtf8 { valid_up_to: usize },
+    NotAscii { offset: usize },
+    UnsupportedCharset(String),
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextError::InvalidUtf8 { valid_up_to } => {
+                write!(f, "body is not valid UTF-8 after byte {}", valid_up_to)
+            }
+            TextError::NotAscii { offset } => write!(f, "non-ASCII body byte at {}", offset),
+            TextError::UnsupportedCharset(charset) => {
+                write!(f, "unsupported charset {:?}", charset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// Failure while reading a message from a stream.
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Parse(ParseError),
+    /// The peer closed the connection before sending a single byte, which
+    /// ends a keep-alive connection cleanly rather than truncating a message.
+    Closed,
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+impl From<ParseError> for ReadError {
+    fn from(err: ParseError) -> Self {
+        ReadError::Parse(err)
+    }
+}
+
+impl From<ReadError> for io::Error {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Io(err) => err,
+            ReadError::Parse(err) => io::Error::new(io::ErrorKind::InvalidData, err),
+            ReadError::Closed => io::Error::from(io::ErrorKind::UnexpectedEof),
+        }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(err) => write!(f, "i/o error: {}", err),
+            ReadError::Parse(err) => err.fmt(f),
+            ReadError::Closed => write!(f, "connection closed"),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(err) => Some(err),
+            ReadError::Parse(err) => Some(err),
+            ReadError::Closed => None,
+        }
+    }
+}
+
+/// Failure of a request sent with [`Client`](crate::client::Client).
+#[derive(Debug)]
+pub enum ClientError {
+    /// The URL or request target does not name a host to connect to.
+    InvalidUrl(String),
+    /// Only plain `http` is spoken; there is no TLS.
+    UnsupportedScheme(String),
+    /// No connection could be made to any address of the host.
+    Connect(io::Error),
+    /// Connecting, sending or waiting for the response took too long.
+    Timeout,
+    Io(io::Error),
+    /// The server's response could not be parsed.
+    Parse(ParseError),
+    /// The server closed the connection without responding.
+    Closed,
+    /// A redirect pointed somewhere that cannot be requested.
+    InvalidRedirect(String),
+    /// More redirects than the client is configured to follow.
+    TooManyRedirects {
+        limit: usize,
+    },
+}
+
+// a socket timeout shows up as either kind depending on the platform
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
+
+impl From<io::Error> for ClientError {
+    fn from(err: io::Error) -> Self {
+        if is_timeout(&err) {
+            return ClientError::Timeout;
+        }
+        // a body that fails to parse surfaces from `Read` as an i/o error
+        match err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<ParseError>())
+        {
+            Some(parse) => ClientError::Parse(parse.clone()),
+            None => ClientError::Io(err),
+        }
+    }
+}
+
+impl From<ReadError> for ClientError {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Io(err) => err.into(),
+            ReadError::Parse(err) => ClientError::Parse(err),
+            ReadError::Closed => ClientError::Closed,
+        }
+    }
+}
+
+impl ClientError {
+    // connection failures are kept apart from failures on an open connection
+    pub(crate) fn connect(err: io::Error) -> Self {
+        if is_timeout(&err) {
+            ClientError::Timeout
+        } else {
+            ClientError::Connect(err)
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::InvalidUrl(url) => write!(f, "invalid URL {:?}", url),
+            ClientError::UnsupportedScheme(scheme) => {
+                write!(f, "unsupported scheme {:?}", scheme)
+            }
+            ClientError::Connect(err) => write!(f, "could not connect: {}", err),
+            ClientError::Timeout => write!(f, "timed out"),
+            ClientError::Io(err) => write!(f, "i/o error: {}", err),
+            ClientError::Parse(err) => write!(f, "invalid response: {}", err),
+            ClientError::Closed => write!(f, "connection closed before a response"),
+            ClientError::InvalidRedirect(location) => {
+                write!(f, "invalid redirect to {:?}", location)
+            }
+            ClientError::TooManyRedirects { limit } => {
+                write!(f, "more than {} redirects", limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Connect(err) | ClientError::Io(err) => Some(err),
+            ClientError::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
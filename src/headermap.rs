@@ -0,0 +1,198 @@
+use crate::error::{InvalidHeader, ParseError};
+use crate::httprequest::is_token;
+
+/// Header fields shared by requests and responses.
+///
+/// Lookups ignore ASCII case, while iteration yields the fields in insertion
+/// order with the casing they were inserted with, so serialization reproduces
+/// what the caller wrote. A name may carry several values (`Set-Cookie`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The first value for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.position(name).map(|i| self.entries[i].1.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Sets `name` to a single value, replacing every existing value in place
+    /// of the first one.
+    pub fn insert(&mut self, name: &str, value: &str) -> Result<(), InvalidHeader> {
+        let value = validate(name, value)?;
+        match self.position(name) {
+            Some(first) => {
+                self.entries[first] = (name.to_string(), value.to_string());
+                let rest = self.entries.split_off(first + 1);
+                self.entries.extend(
+                    rest.into_iter()
+                        .filter(|(n, _)| !n.eq_ignore_ascii_case(name)),
+                );
+            }
+            None => self.entries.push((name.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Adds a value for `name`, keeping any existing ones.
+    pub fn append(&mut self, name: &str, value: &str) -> Result<(), InvalidHeader> {
+        let value = validate(name, value)?;
+        self.entries.push((name.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    /// Removes every value for `name`, returning the first one.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let first = self.position(name)?;
+        let (_, value) = self.entries.remove(first);
+        self.entries.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        Some(value)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.entries.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+}
+
+pub type Iter<'a> = std::iter::Map<
+    std::slice::Iter<'a, (String, String)>,
+    fn(&'a (String, String)) -> (&'a str, &'a str),
+>;
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// names must be tokens and values field-content (RFC 9110, section 5.5);
+// surrounding whitespace is not part of the value and is trimmed
+fn validate<'v>(name: &str, value: &'v str) -> Result<&'v str, InvalidHeader> {
+    if !is_token(name) {
+        return Err(InvalidHeader::Name(name.to_string()));
+    }
+    let value = value.trim_matches([' ', '\t']);
+    if !is_field_value(value) {
+        return Err(InvalidHeader::Value(value.to_string()));
+    }
+    Ok(value)
+}
+
+// a `name: value` line of a head or trailer section, starting at `offset`
+pub(crate) fn parse_field_line(line: &str, offset: usize) -> Result<(&str, &str), ParseError> {
+    // only the first colon ends the name: values such as `localhost:8080`,
+    // URLs and timestamps contain colons of their own
+    let (name, value) = line
+        .split_once(':')
+        .ok_or(ParseError::BadHeaderLine { offset })?;
+    // this also rejects whitespace between the name and the colon (RFC 9112, section 5.1)
+    let value = validate(name, value).map_err(|_| ParseError::BadHeaderLine { offset })?;
+    Ok((name, value))
+}
+
+// no control characters other than HTAB
+pub(crate) fn is_field_value(value: &str) -> bool {
+    !value.bytes().any(|b| (b < 0x20 && b != b'\t') || b == 0x7f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_is_case_insensitive() {
+        let mut h = HeaderMap::new();
+        h.insert("Content-Type", "text/html").unwrap();
+        assert_eq!(h.get("content-type"), Some("text/html"));
+        assert_eq!(h.get("CONTENT-TYPE"), Some("text/html"));
+        assert!(h.contains_key("content-TYPE"));
+
+        h.insert("content-type", "application/json").unwrap();
+        assert_eq!(h.len(), 1);
+        assert_eq!(h.get("Content-Type"), Some("application/json"));
+    }
+
+    #[test]
+    fn test_multiple_values() {
+        let mut h = HeaderMap::new();
+        h.append("Set-Cookie", "a=1").unwrap();
+        h.append("Accept", "text/html").unwrap();
+        h.append("set-cookie", "b=2").unwrap();
+        assert_eq!(h.get("Set-Cookie"), Some("a=1"));
+        assert_eq!(
+            h.get_all("SET-COOKIE").collect::<Vec<_>>(),
+            vec!["a=1", "b=2"]
+        );
+
+        h.insert("Set-Cookie", "c=3").unwrap();
+        assert_eq!(
+            h.iter().collect::<Vec<_>>(),
+            vec![("Set-Cookie", "c=3"), ("Accept", "text/html")]
+        );
+
+        assert_eq!(h.remove("accept"), Some("text/html".to_string()));
+        assert_eq!(h.remove("accept"), None);
+        assert_eq!(h.len(), 1);
+    }
+
+    #[test]
+    fn test_preserves_order_and_casing() {
+        let mut h = HeaderMap::new();
+        h.append("x-first", "1").unwrap();
+        h.append("X-Second", "2").unwrap();
+        h.append("X-THIRD", "3").unwrap();
+        let names: Vec<&str> = (&h).into_iter().map(|(n, _)| n).collect();
+        assert_eq!(names, vec!["x-first", "X-Second", "X-THIRD"]);
+    }
+
+    #[test]
+    fn test_validation() {
+        let mut h = HeaderMap::new();
+        assert_eq!(
+            h.append("Bad Name", "x"),
+            Err(InvalidHeader::Name("Bad Name".to_string()))
+        );
+        assert_eq!(
+            h.append("X-Injected", "a\r\nSet-Cookie: evil"),
+            Err(InvalidHeader::Value("a\r\nSet-Cookie: evil".to_string()))
+        );
+        assert!(h.append("", "x").is_err());
+        assert!(h.is_empty());
+
+        h.append("X-Padded", "  value\t").unwrap();
+        assert_eq!(h.get("x-padded"), Some("value"));
+    }
+}
@@ -1,142 +1,1213 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, IoSlice, Write};
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq)]
+use crate::chunked::{self, ChunkedWriter};
+use crate::error::{ParseError, TextError};
+pub use crate::headermap::HeaderMap;
+use crate::httpresponse::{fields_len, push_content_length, push_fields, write_all_vectored};
+use crate::parser::{Framing, ParserConfig, RequestParser, Status};
+use crate::resource::Form;
+pub use crate::resource::{DecodeOptions, QueryParams, Resource};
+
+pub(crate) const MAX_HEADER_LINE: usize = 8 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Method {
     Get,
+    Head,
     Post,
-    Uninitialized,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    /// Any other syntactically valid method token, e.g. WebDAV's `PROPFIND`.
+    Extension(String),
 }
 
-impl From<&str> for Method {
-    fn from(value: &str) -> Self {
-        match value.to_lowercase().as_str() {
-            "get" => Method::Get,
-            "post" => Method::Post,
-            _ => Method::Uninitialized,
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Connect => "CONNECT",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Patch => "PATCH",
+            Method::Extension(token) => token,
         }
     }
+
+    /// Safe methods are read-only by definition (RFC 9110, section 9.2.1).
+    pub fn is_safe(&self) -> bool {
+        matches!(
+            self,
+            Method::Get | Method::Head | Method::Options | Method::Trace
+        )
+    }
+
+    /// Idempotent methods may be retried automatically (RFC 9110, section 9.2.2).
+    pub fn is_idempotent(&self) -> bool {
+        self.is_safe() || matches!(self, Method::Put | Method::Delete)
+    }
+}
+
+impl FromStr for Method {
+    type Err = ParseError;
+
+    // method tokens are case-sensitive, so "get" is an extension method and not GET
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "GET" => Ok(Method::Get),
+            "HEAD" => Ok(Method::Head),
+            "POST" => Ok(Method::Post),
+            "PUT" => Ok(Method::Put),
+            "DELETE" => Ok(Method::Delete),
+            "CONNECT" => Ok(Method::Connect),
+            "OPTIONS" => Ok(Method::Options),
+            "TRACE" => Ok(Method::Trace),
+            "PATCH" => Ok(Method::Patch),
+            token if is_token(token) => Ok(Method::Extension(token.to_string())),
+            _ => Err(ParseError::InvalidMethod { offset: 0 }),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Checks `s` against the RFC 9110 `token` grammar used by methods and field names.
+pub(crate) fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes().all(|b| {
+            b.is_ascii_alphanumeric()
+                || matches!(
+                    b,
+                    b'!' | b'#'
+                        | b'$'
+                        | b'%'
+                        | b'&'
+                        | b'\''
+                        | b'*'
+                        | b'+'
+                        | b'-'
+                        | b'.'
+                        | b'^'
+                        | b'_'
+                        | b'`'
+                        | b'|'
+                        | b'~'
+                )
+        })
+}
+
+/// The client connection preface that opens every prior-knowledge HTTP/2 connection.
+pub const HTTP2_PREFACE: &str = "PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+// whether `line` is the request line that opens the HTTP/2 preface
+pub(crate) fn is_preface_line(line: &str) -> bool {
+    HTTP2_PREFACE
+        .split_once("\r\n")
+        .is_some_and(|(first, _)| first == line)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Version {
+    V1_0,
     V1_1,
     V2_0,
-    Uninitialized,
 }
 
-impl From<&str> for Version {
-    fn from(value: &str) -> Self {
-        match value.to_lowercase().as_str() {
-            "http/1.1" => Version::V1_1,
-            _ => Version::Uninitialized,
+impl Version {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::V1_0 => "HTTP/1.0",
+            Version::V1_1 => "HTTP/1.1",
+            Version::V2_0 => "HTTP/2.0",
         }
     }
+
+    /// HTTP/1.1 connections are persistent unless `Connection: close` is sent,
+    /// HTTP/1.0 connections close unless `Connection: keep-alive` is sent.
+    pub fn keep_alive_by_default(&self) -> bool {
+        !matches!(self, Version::V1_0)
+    }
+
+    /// Transfer codings such as chunked were introduced with HTTP/1.1.
+    pub fn supports_chunked(&self) -> bool {
+        matches!(self, Version::V1_1)
+    }
 }
 
-use std::collections::HashMap;
-#[derive(Debug, PartialEq)]
-pub enum Resource {
-    Path(String),
+impl FromStr for Version {
+    type Err = ParseError;
+
+    // the version string is case-sensitive (RFC 9112, section 2.3)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HTTP/1.0" => Ok(Version::V1_0),
+            "HTTP/1.1" => Ok(Version::V1_1),
+            "HTTP/2.0" => Ok(Version::V2_0),
+            _ => Err(ParseError::UnsupportedVersion { offset: 0 }),
+        }
+    }
 }
-impl From<&str> for Resource {
-    fn from(s: &str) -> Self {
-        Resource::Path(s.to_string())
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Resolves persistence from a `Connection` header value and the message version.
+pub(crate) fn keep_alive(connection: Option<&str>, version: Version) -> bool {
+    let has_option = |option: &str| {
+        connection.is_some_and(|value| {
+            value
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case(option))
+        })
+    };
+    if has_option("close") {
+        false
+    } else if has_option("keep-alive") {
+        true
+    } else {
+        version.keep_alive_by_default()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HttpRequest {
     pub method: Method,
     pub version: Version,
     pub resource: Resource,
-    pub headers: HashMap<String, String>,
-    pub msg_body: String,
+    pub headers: HeaderMap,
+    pub msg_body: Vec<u8>,
+    /// Fields sent after a chunked body; kept apart from `headers` because
+    /// they arrive too late to influence how the request is handled.
+    pub trailers: HeaderMap,
+    // both Transfer-Encoding and Content-Length were received, after which
+    // the connection must close (RFC 9112, section 6.1)
+    pub(crate) must_close: bool,
+}
+
+impl TryFrom<&[u8]> for HttpRequest {
+    type Error = ParseError;
+
+    fn try_from(req: &[u8]) -> Result<Self, Self::Error> {
+        HttpRequest::parse(req)
+    }
 }
+
+/// A thin wrapper over [`HttpRequest::parse`] for tests and literals, which
+/// panics on a malformed request; parse untrusted input with `parse` or the
+/// `TryFrom<&[u8]>` conversion. `TryFrom<&str>` cannot be offered as well,
+/// since the standard library derives it from this impl.
 impl From<&str> for HttpRequest {
     fn from(req: &str) -> Self {
-        let mut parsed_method = Method::Uninitialized;
-        let mut parsed_version = Version::Uninitialized;
-        let mut parsed_resource = Resource::Path("".to_string());
-        let mut parsed_headers = HashMap::new();
-        let mut parsed_msg_body = String::new();
-
-        for line in req.lines() {
-            if line.contains("HTTP") {
-                let (method, resource, version) = HttpRequest::process_req_line(line);
-                parsed_method = method;
-                parsed_resource = resource;
-                parsed_version = version;
-            } else if line.contains(":") {
-                let (key, value) = HttpRequest::process_header_line(line);
-                parsed_headers.insert(key, value);
-            } else if line.is_empty() {
-                continue;
-            } else {
-                parsed_msg_body = line.to_string();
+        HttpRequest::parse(req).unwrap_or_else(|err| panic!("invalid HTTP request: {}", err))
+    }
+}
+
+/// Serializes the request in memory, failing as [`HttpRequest::write_to`] does.
+///
+/// This is `TryFrom` rather than `From` because the fields are public, so a
+/// request can hold a target or trailers that HTTP/1.x cannot represent; an
+/// infallible conversion could only drop them or return a truncated message.
+impl TryFrom<HttpRequest> for Vec<u8> {
+    type Error = io::Error;
+
+    fn try_from(req: HttpRequest) -> Result<Self, Self::Error> {
+        let mut buf = Vec::new();
+        req.write_to(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl HttpRequest {
+    /// An HTTP/1.1 request for `resource` with no headers and an empty body.
+    pub fn new(method: Method, resource: Resource) -> Self {
+        Self {
+            method,
+            version: Version::V1_1,
+            resource,
+            headers: HeaderMap::new(),
+            msg_body: Vec::new(),
+            trailers: HeaderMap::new(),
+            must_close: false,
+        }
+    }
+
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(req: &T) -> Result<Self, ParseError> {
+        Self::parse_with(req, ParserConfig::default())
+    }
+
+    /// Parses a request, refusing it as soon as any of the limits in `config`
+    /// is exceeded.
+    pub fn parse_with<T: AsRef<[u8]> + ?Sized>(
+        req: &T,
+        config: ParserConfig,
+    ) -> Result<Self, ParseError> {
+        let req = req.as_ref();
+        let mut parser = RequestParser::with_config(config);
+        let Status::Complete(head_len) = parser.parse(req)? else {
+            return Err(ParseError::IncompleteHead { offset: req.len() });
+        };
+        let (mut request, framing) = parser.into_parts()?;
+
+        let body = &req[head_len..];
+        match framing {
+            Framing::Chunked => {
+                let decoded = chunked::decode_limited(body, head_len, config)?;
+                request.msg_body = decoded.body;
+                request.trailers = decoded.trailers;
+            }
+            Framing::Length(expected) => {
+                if body.len() < expected {
+                    return Err(ParseError::TruncatedBody {
+                        expected,
+                        received: body.len(),
+                    });
+                }
+                request.msg_body = body[..expected].to_vec();
             }
         }
+        Ok(request)
+    }
 
-        HttpRequest {
-            method: parsed_method,
-            version: parsed_version,
-            resource: parsed_resource,
-            headers: parsed_headers,
-            msg_body: parsed_msg_body,
+    pub fn bytes(&self) -> &[u8] {
+        &self.msg_body
+    }
+
+    /// The body as text, decoded with the charset declared in `Content-Type`
+    /// (UTF-8 when none is given).
+    pub fn text(&self) -> Result<Cow<'_, str>, TextError> {
+        decode_text(&self.msg_body, self.headers.get("Content-Type"))
+    }
+
+    /// Whether the connection should stay open after this request; never
+    /// after one that sent both `Transfer-Encoding` and `Content-Length`.
+    pub fn keep_alive(&self) -> bool {
+        if self.must_close {
+            return false;
+        }
+        let connection = self.headers.get("Connection");
+        keep_alive(connection, self.version)
+    }
+
+    /// Writes the request as HTTP/1.x.
+    ///
+    /// Framing is derived from the body and trailers, replacing any
+    /// `Content-Length` or `Transfer-Encoding` in `headers`: trailers need a
+    /// chunked body, otherwise a body gets its length, as does an empty body
+    /// for methods that expect one. When `Host` is missing it is sent first,
+    /// taken from the target or left empty if the target has no authority
+    /// (RFC 9112, section 3.2). A request line that would not parse back, such
+    /// as an asterisk-form target on GET, is refused with `InvalidInput`, as
+    /// are trailers on HTTP/1.0, which has no chunked body to carry them.
+    pub fn write_to<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        if !self.trailers.is_empty() && !self.version.supports_chunked() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "trailers need a chunked body, which HTTP/1.0 lacks",
+            ));
+        }
+        let target = self.resource.to_string();
+        let mut head = Vec::with_capacity(self.head_len(&target));
+        self.write_head(&mut head, &target)?;
+        match self.outbound_framing() {
+            Some(Framing::Chunked) => {
+                w.write_all(&head)?;
+                let mut chunked = ChunkedWriter::new(w);
+                chunked.write_all(&self.msg_body)?;
+                chunked.finish_with_trailers(&self.trailers)?;
+                Ok(())
+            }
+            _ => write_all_vectored(w, &mut [IoSlice::new(&head), IoSlice::new(&self.msg_body)]),
+        }
+    }
+
+    // trailers can only travel after a chunked body; `write_to` has already
+    // refused them on HTTP/1.0
+    fn outbound_framing(&self) -> Option<Framing> {
+        let expects_body = matches!(self.method, Method::Post | Method::Put | Method::Patch);
+        if !self.trailers.is_empty() {
+            Some(Framing::Chunked)
+        } else if !self.msg_body.is_empty() || expects_body {
+            Some(Framing::Length(self.msg_body.len()))
+        } else {
+            None
         }
     }
+
+    // request line, Host, the remaining headers, the framing header and the empty line
+    fn write_head(&self, buf: &mut Vec<u8>, target: &str) -> io::Result<()> {
+        let start = buf.len();
+        buf.extend_from_slice(self.method.as_str().as_bytes());
+        buf.push(b' ');
+        buf.extend_from_slice(target.as_bytes());
+        buf.push(b' ');
+        buf.extend_from_slice(self.version.as_str().as_bytes());
+        // the fields are public, so nothing else stops a target holding a CRLF
+        std::str::from_utf8(&buf[start..])
+            .map_err(|_| ParseError::MalformedRequestLine { offset: 0 })
+            .and_then(|line| RequestLine::parse(line, 0))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        buf.extend_from_slice(b"\r\n");
+
+        if !self.headers.contains_key("Host") {
+            buf.extend_from_slice(b"Host: ");
+            buf.extend_from_slice(self.resource.authority().unwrap_or("").as_bytes());
+            buf.extend_from_slice(b"\r\n");
+        }
+        push_fields(buf, &self.headers, None);
+        match self.outbound_framing() {
+            Some(Framing::Length(len)) => push_content_length(buf, len as u64),
+            Some(Framing::Chunked) => buf.extend_from_slice(b"Transfer-Encoding: chunked\r\n"),
+            None => {}
+        }
+        buf.extend_from_slice(b"\r\n");
+        Ok(())
+    }
+
+    // an upper bound on what `write_head` produces
+    fn head_len(&self, target: &str) -> usize {
+        let request_line = self.method.as_str().len() + target.len() + "  HTTP/1.1\r\n".len();
+        let host = "Host: \r\n".len() + self.resource.authority().map_or(0, str::len);
+        request_line + host + fields_len(&self.headers)
+    }
+
+    pub(crate) fn process_req_line(
+        s: &str,
+        offset: usize,
+    ) -> Result<(Method, Resource, Version), ParseError> {
+        let line = RequestLine::parse(s, offset)?;
+        // already known to be a token, so this cannot fail
+        let method = line
+            .method
+            .parse()
+            .map_err(|_| ParseError::InvalidMethod { offset })?;
+        let resource = Resource::from_form(line.target, line.form);
+        Ok((method, resource, line.version))
+    }
 }
 
-impl HttpRequest {
-    fn process_req_line(s: &str) -> (Method, Resource, Version) {
-        // ssplit the words by whitespace
-        let mut words = s.split_whitespace();
-        let method: Method = words.next().unwrap().trim().into();
-        let resource: Resource = words.next().unwrap().trim().into();
-        let version: Version = words.next().unwrap().trim().into();
+/// The parts of a request line, borrowed from it after validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RequestLine<'a> {
+    pub(crate) method: &'a str,
+    pub(crate) target: &'a str,
+    pub(crate) form: Form,
+    pub(crate) version: Version,
+}
+
+impl<'a> RequestLine<'a> {
+    pub(crate) fn parse(s: &'a str, offset: usize) -> Result<Self, ParseError> {
+        // the request line is exactly three words separated by single spaces
+        let mut words = s.splitn(3, ' ');
+        let (Some(method), Some(target), Some(version)) =
+            (words.next(), words.next(), words.next())
+        else {
+            return Err(ParseError::MalformedRequestLine { offset });
+        };
+        if method.is_empty() || target.is_empty() || version.contains(' ') {
+            return Err(ParseError::MalformedRequestLine { offset });
+        }
+
+        let target_offset = offset + method.len() + 1;
+        let version_offset = target_offset + target.len() + 1;
+        if !is_token(method) {
+            return Err(ParseError::InvalidMethod { offset });
+        }
+        let form = Form::classify(target, target_offset)?;
+        // authority-form belongs to CONNECT alone and asterisk-form to OPTIONS alone
+        let form_matches = match (method, form) {
+            ("CONNECT", form) => form == Form::Authority,
+            (_, Form::Authority) => false,
+            (method, Form::Asterisk) => method == "OPTIONS",
+            _ => true,
+        };
+        if !form_matches {
+            return Err(ParseError::InvalidTarget {
+                offset: target_offset,
+            });
+        }
+        let version = match version.parse() {
+            Ok(Version::V2_0) | Err(_) => {
+                return Err(ParseError::UnsupportedVersion {
+                    offset: version_offset,
+                });
+            }
+            Ok(version) => version,
+        };
+
+        Ok(Self {
+            method,
+            target,
+            form,
+            version,
+        })
+    }
+}
+
+/// The framing fields seen so far in a head, checked as each one arrives.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FramingCheck {
+    length: Option<usize>,
+    // chunked is the final transfer coding seen so far
+    chunked: bool,
+    // chunked was applied, though a response may list codings after it
+    chunked_once: bool,
+    // any transfer coding was seen, chunked or not
+    coded: bool,
+    response: bool,
+    max_body: usize,
+}
+
+impl FramingCheck {
+    pub(crate) fn new(max_body: usize) -> Self {
+        Self {
+            length: None,
+            chunked: false,
+            chunked_once: false,
+            coded: false,
+            response: false,
+            max_body,
+        }
+    }
+
+    // a response may end its codings with something other than chunked and
+    // run until the connection closes instead (RFC 9112, section 6.3)
+    pub(crate) fn for_response(max_body: usize) -> Self {
+        Self {
+            response: true,
+            ..Self::new(max_body)
+        }
+    }
+
+    pub(crate) fn field(
+        &mut self,
+        name: &str,
+        value: &str,
+        version: Version,
+        offset: usize,
+    ) -> Result<(), ParseError> {
+        if name.eq_ignore_ascii_case("Transfer-Encoding") {
+            // a request's only coding must be chunked, applied once; anything
+            // else leaves the body length ambiguous, the root of request
+            // smuggling, as does a transfer coding on HTTP/1.0 in either direction
+            if !version.supports_chunked() {
+                return Err(ParseError::InvalidTransferEncoding { offset });
+            }
+            if self.response {
+                self.codings(value, offset)?;
+            } else if !value.eq_ignore_ascii_case("chunked") || self.chunked {
+                return Err(ParseError::InvalidTransferEncoding { offset });
+            } else {
+                self.chunked = true;
+            }
+            self.coded = true;
+        }
+        if name.eq_ignore_ascii_case("Content-Length") {
+            // repeated values are only tolerated when they all agree
+            let length = content_length(value);
+            if length.is_none() || self.length.is_some_and(|l| Some(l) != length) {
+                return Err(ParseError::InvalidContentLength { offset });
+            }
+            if length.is_some_and(|l| l > self.max_body) {
+                return Err(ParseError::BodyTooLarge { offset });
+            }
+            self.length = length;
+        }
+        Ok(())
+    }
 
-        return (method, resource, version);
+    // the codings of a response's Transfer-Encoding field, which continue
+    // any listed by an earlier one; chunked may still appear only once
+    fn codings(&mut self, value: &str, offset: usize) -> Result<(), ParseError> {
+        for coding in value.split(',').map(|c| c.trim_matches([' ', '\t'])) {
+            let chunked = coding.eq_ignore_ascii_case("chunked");
+            if !is_token(coding) || (chunked && self.chunked_once) {
+                return Err(ParseError::InvalidTransferEncoding { offset });
+            }
+            self.chunked = chunked;
+            self.chunked_once |= chunked;
+        }
+        Ok(())
     }
 
-    fn process_header_line(s: &str) -> (String, String) {
-        let mut header_items = s.split(":");
-        let mut key = String::from("");
-        let mut value = String::from("");
+    // both framing fields were sent, which leaves the connection unusable
+    // for another message
+    pub(crate) fn is_conflicting(&self) -> bool {
+        self.coded && self.length.is_some()
+    }
 
-        if let Some(k) = header_items.next() {
-            key = k.trim().to_string();
+    // without Content-Length or a transfer coding a request has no body (RFC 9112, section 6.3)
+    pub(crate) fn framing(&self) -> Framing {
+        match (self.chunked, self.length) {
+            (true, _) => Framing::Chunked,
+            (false, length) => Framing::Length(length.unwrap_or(0)),
         }
-        if let Some(v) = header_items.next() {
-            value = v.trim().to_string()
+    }
+
+    // a response without either field, or whose final coding is not
+    // chunked, is delimited by the connection closing, given as `None`
+    pub(crate) fn response_framing(&self) -> Option<Framing> {
+        match (self.chunked, self.coded, self.length) {
+            (true, _, _) => Some(Framing::Chunked),
+            (false, true, _) => None,
+            (false, false, length) => length.map(Framing::Length),
         }
-        return (key, value);
     }
 }
 
+pub(crate) fn decode_text<'b>(
+    body: &'b [u8],
+    content_type: Option<&str>,
+) -> Result<Cow<'b, str>, TextError> {
+    let charset = content_type
+        .into_iter()
+        .flat_map(|value| value.split(';').skip(1))
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_ascii_lowercase());
+
+    match charset.as_deref() {
+        None | Some("utf-8") | Some("utf8") => std::str::from_utf8(body)
+            .map(Cow::Borrowed)
+            .map_err(|e| TextError::InvalidUtf8 {
+                valid_up_to: e.valid_up_to(),
+            }),
+        Some("us-ascii") | Some("ascii") => match body.iter().position(|b| !b.is_ascii()) {
+            Some(offset) => Err(TextError::NotAscii { offset }),
+            None => Ok(String::from_utf8_lossy(body)),
+        },
+        // every byte maps to the code point of the same value
+        Some("iso-8859-1") | Some("latin1") => {
+            Ok(Cow::Owned(body.iter().map(|&b| b as char).collect()))
+        }
+        Some(other) => Err(TextError::UnsupportedCharset(other.to_string())),
+    }
+}
+
+// parses a Content-Length value, which may be a list of identical lengths
+pub(crate) fn content_length(value: &str) -> Option<usize> {
+    // `usize::from_str` would also accept a leading `+`
+    let mut lengths = value.split(',').map(|v| {
+        let v = v.trim_matches([' ', '\t']);
+        v.bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| v.parse::<usize>().ok())
+            .flatten()
+    });
+    let first = lengths.next()??;
+    lengths.all(|l| l == Some(first)).then_some(first)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::httprequestref::HttpRequestRef;
+    use crate::httpresponse::HttpResponse;
+
     #[test]
     fn test_method_into() {
-        let m: Method = "GET".into();
+        let m: Method = "GET".parse().unwrap();
         assert_eq!(m, Method::Get)
     }
 
+    #[test]
+    fn test_all_standard_methods() {
+        for name in [
+            "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+        ] {
+            let m: Method = name.parse().unwrap();
+            assert_eq!(m.as_str(), name);
+            assert_eq!(m.to_string(), name);
+            assert!(!matches!(m, Method::Extension(_)));
+        }
+    }
+
+    #[test]
+    fn test_extension_methods_are_case_sensitive() {
+        let m: Method = "PROPFIND".parse().unwrap();
+        assert_eq!(m, Method::Extension("PROPFIND".to_string()));
+        assert_eq!(m.to_string(), "PROPFIND");
+
+        let m: Method = "get".parse().unwrap();
+        assert_eq!(m, Method::Extension("get".to_string()));
+
+        assert!("GET /".parse::<Method>().is_err());
+        assert!("".parse::<Method>().is_err());
+    }
+
+    #[test]
+    fn test_method_properties() {
+        assert!(Method::Get.is_safe());
+        assert!(Method::Head.is_idempotent());
+        assert!(!Method::Put.is_safe());
+        assert!(Method::Put.is_idempotent());
+        assert!(Method::Delete.is_idempotent());
+        assert!(!Method::Post.is_idempotent());
+        assert!(!Method::Patch.is_idempotent());
+        assert!(!Method::Extension("PROPFIND".to_string()).is_safe());
+    }
+
     #[test]
     fn test_version_into() {
-        let v: Version = "HTTP/1.1".into();
+        let v: Version = "HTTP/1.1".parse().unwrap();
         assert_eq!(v, Version::V1_1);
     }
+    #[test]
+    fn test_version_round_trip() {
+        for name in ["HTTP/1.0", "HTTP/1.1"] {
+            let v: Version = name.parse().unwrap();
+            assert_eq!(v.to_string(), name);
+        }
+        assert!("http/1.1".parse::<Version>().is_err());
+        assert!(Version::V1_1.supports_chunked());
+        assert!(!Version::V1_0.supports_chunked());
+    }
+
+    #[test]
+    fn test_parse_http_1_0_request() {
+        let http_req = HttpRequest::parse("GET /health HTTP/1.0\r\n\r\n").unwrap();
+        assert_eq!(http_req.version, Version::V1_0);
+        assert!(!http_req.keep_alive());
+
+        let http_req =
+            HttpRequest::parse("GET /health HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n").unwrap();
+        assert!(http_req.keep_alive());
+
+        let http_req =
+            HttpRequest::parse("GET /health HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        assert!(!http_req.keep_alive());
+    }
+
+    #[test]
+    fn test_http2_preface_is_recognized() {
+        let err = HttpRequest::parse(HTTP2_PREFACE).unwrap_err();
+        assert_eq!(err, ParseError::Http2Preface);
+
+        let err = HttpRequest::parse("GET / HTTP/2.0\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::UnsupportedVersion { offset: 6 });
+    }
+
+    #[test]
+    fn test_request_target_forms() {
+        let http_req = HttpRequest::parse("GET /search?q=a&page=2 HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(http_req.resource.path(), "/search");
+        assert_eq!(
+            http_req.resource.query_params().unwrap().get("page"),
+            Some("2")
+        );
+
+        let http_req = HttpRequest::parse("CONNECT example.com:443 HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(
+            http_req.resource,
+            Resource::Authority("example.com:443".to_string())
+        );
+
+        let http_req = HttpRequest::parse("OPTIONS * HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(http_req.resource, Resource::Asterisk);
+
+        let http_req = HttpRequest::parse("GET http://example.com/x HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(http_req.resource.authority(), Some("example.com"));
+    }
+
+    #[test]
+    fn test_request_target_must_match_method() {
+        let err = HttpRequest::parse("GET * HTTP/1.1\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::InvalidTarget { offset: 4 });
+
+        let err = HttpRequest::parse("CONNECT / HTTP/1.1\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::InvalidTarget { offset: 8 });
+
+        let err = HttpRequest::parse("GET example.com:80 HTTP/1.1\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::InvalidTarget { offset: 4 });
+
+        let err = HttpRequest::parse("GET /index.html#top HTTP/1.1\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::InvalidTarget { offset: 15 });
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid HTTP request")]
+    fn test_from_str_panics_on_a_malformed_request() {
+        let _ = HttpRequest::from("GET /home\r\n\r\n");
+    }
+
     #[test]
     fn test_parse_http_request() {
-        let req =
-            "GET /home HTTP/1.1\r\nHost: localhost\r\nUser-Agent: test\r\n\r\nThis is the body";
-        let http_req: HttpRequest = req.into();
+        let req = "GET /home HTTP/1.1\r\nHost: localhost\r\nUser-Agent: test\r\nContent-Length: 16\r\n\r\nThis is the body";
+        let http_req = HttpRequest::from(req);
 
         assert_eq!(http_req.method, Method::Get);
         assert_eq!(http_req.version, Version::V1_1);
-        assert_eq!(http_req.resource, Resource::Path("/home".to_string()));
+        assert_eq!(
+            http_req.resource,
+            Resource::Path {
+                path: "/home".to_string(),
+                query: None
+            }
+        );
         assert_eq!(http_req.headers.get("Host").unwrap(), "localhost");
         assert_eq!(http_req.headers.get("User-Agent").unwrap(), "test");
-        assert_eq!(http_req.msg_body, "This is the body");
+        assert_eq!(http_req.text().unwrap(), "This is the body");
+    }
+
+    #[test]
+    fn test_headers_are_case_insensitive_and_multi_valued() {
+        let req = "GET / HTTP/1.1\r\ncontent-type: text/plain\r\nAccept: text/html\r\nAccept: application/json\r\n\r\n";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(http_req.headers.get("Content-Type"), Some("text/plain"));
+        assert_eq!(
+            http_req.headers.get_all("accept").collect::<Vec<_>>(),
+            vec!["text/html", "application/json"]
+        );
+
+        let err = HttpRequest::parse("GET / HTTP/1.1\r\nBad Name: x\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::BadHeaderLine { offset: 16 });
+    }
+
+    #[test]
+    fn test_header_values_keep_their_colons() {
+        let req = "GET / HTTP/1.1\r\nHost: localhost:8080\r\nReferer: https://example.com/x\r\nIf-Modified-Since: Tue, 15 Nov 1994 12:45:26 GMT\r\nX-Empty:\r\n\r\n";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(http_req.headers.get("Host"), Some("localhost:8080"));
+        assert_eq!(
+            http_req.headers.get("Referer"),
+            Some("https://example.com/x")
+        );
+        assert_eq!(
+            http_req.headers.get("If-Modified-Since"),
+            Some("Tue, 15 Nov 1994 12:45:26 GMT")
+        );
+        assert_eq!(http_req.headers.get("X-Empty"), Some(""));
+    }
+
+    #[test]
+    fn test_whitespace_before_colon_is_rejected() {
+        let err = HttpRequest::parse("GET / HTTP/1.1\r\nHost : localhost\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::BadHeaderLine { offset: 16 });
+
+        let err = HttpRequest::parse("GET / HTTP/1.1\r\nHost\t: localhost\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::BadHeaderLine { offset: 16 });
+    }
+
+    #[test]
+    fn test_obsolete_line_folding() {
+        let req =
+            "GET / HTTP/1.1\r\nX-Long: first\r\n  second\r\n\tthird\r\nHost: localhost\r\n\r\n";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(http_req.headers.get("X-Long"), Some("first second third"));
+        assert_eq!(http_req.headers.get("Host"), Some("localhost"));
+    }
+
+    #[test]
+    fn test_short_request_line_is_an_error() {
+        let err = HttpRequest::parse("GET HTTP\r\nHost: localhost\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::MalformedRequestLine { offset: 0 });
+    }
+
+    #[test]
+    fn test_invalid_method_and_version() {
+        let err = HttpRequest::parse("GE(T /home HTTP/1.1\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::InvalidMethod { offset: 0 });
+
+        let err = HttpRequest::parse("GET /home HTTP/9.9\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::UnsupportedVersion { offset: 10 });
+    }
+
+    #[test]
+    fn test_bad_header_lines() {
+        let err = HttpRequest::parse("GET / HTTP/1.1\r\n: nameless\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::BadHeaderLine { offset: 16 });
+
+        let req = format!(
+            "GET / HTTP/1.1\r\nX-Big: {}\r\n\r\n",
+            "a".repeat(MAX_HEADER_LINE)
+        );
+        let err = HttpRequest::parse(&req).unwrap_err();
+        assert_eq!(err, ParseError::HeaderTooLarge { offset: 16 });
+    }
+
+    #[test]
+    fn test_body_is_read_by_content_length() {
+        let body = "{\"a\": 1,\r\n\r\n \"b\": \"x:y\"}\nlast line";
+        let req = format!(
+            "POST /submit HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let http_req = HttpRequest::parse(&req).unwrap();
+        assert_eq!(http_req.text().unwrap(), body);
+        assert_eq!(http_req.headers.len(), 2);
+
+        // bytes past Content-Length belong to the next request on the connection
+        let req = "POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcGET / HTTP/1.1\r\n\r\n";
+        assert_eq!(HttpRequest::parse(req).unwrap().text().unwrap(), "abc");
+
+        let req = "GET / HTTP/1.1\r\nHost: localhost\r\n\r\nignored";
+        assert_eq!(HttpRequest::parse(req).unwrap().text().unwrap(), "");
+    }
+
+    #[test]
+    fn test_invalid_content_length() {
+        let err = HttpRequest::parse("POST / HTTP/1.1\r\nContent-Length: 1x\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::InvalidContentLength { offset: 17 });
+
+        let err = HttpRequest::parse("POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n").unwrap_err();
+        assert_eq!(err, ParseError::InvalidContentLength { offset: 17 });
+
+        let req = "POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\nabcd";
+        let err = HttpRequest::parse(req).unwrap_err();
+        assert_eq!(err, ParseError::InvalidContentLength { offset: 36 });
+
+        let req = "POST / HTTP/1.1\r\nContent-Length: 3, 3\r\nContent-Length: 3\r\n\r\nabc";
+        assert_eq!(HttpRequest::parse(req).unwrap().text().unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_chunked_body() {
+        let req = "POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTrailer: Digest\r\n\r\n5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nDigest: sha-256=abc\r\n\r\n";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(http_req.text().unwrap(), "hello world");
+        assert_eq!(http_req.trailers.get("Digest"), Some("sha-256=abc"));
+        assert!(!http_req.headers.contains_key("Digest"));
+    }
+
+    #[test]
+    fn test_transfer_encoding_overrides_content_length() {
+        let req = "POST / HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(http_req.text().unwrap(), "hello");
+        assert!(!http_req.headers.contains_key("Content-Length"));
+        // but the conflict means the connection must close afterwards
+        assert!(!http_req.keep_alive());
+        assert!(!HttpRequestRef::parse(req.as_bytes()).unwrap().keep_alive());
+        assert!(
+            !HttpRequestRef::parse(req.as_bytes())
+                .unwrap()
+                .into_owned()
+                .keep_alive()
+        );
+
+        // without touching the Connection field that was received
+        let req = "POST / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n0\r\n\r\n";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(http_req.headers.get("Connection"), Some("Upgrade"));
+        assert!(!http_req.keep_alive());
+        let owned = HttpRequestRef::parse(req.as_bytes()).unwrap().into_owned();
+        assert_eq!(owned, http_req);
+
+        let req = "POST / HTTP/1.1\r\nConnection: keep-alive\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n0\r\n\r\n";
+        assert!(!HttpRequest::parse(req).unwrap().keep_alive());
+        let req = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+        assert!(HttpRequest::parse(req).unwrap().keep_alive());
+
+        let res =
+            b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+        let response = HttpResponse::parse(res, &Method::Get).unwrap();
+        assert!(!response.keep_alive());
+        assert!(!response.headers().contains_key("Connection"));
+    }
+
+    #[test]
+    fn test_ambiguous_transfer_encodings_are_rejected() {
+        let cases = [
+            "POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked, chunked\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: xchunked\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding:\r\n\r\n",
+            "POST / HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n",
+        ];
+        for req in cases {
+            let err = HttpRequest::parse(req).unwrap_err();
+            assert!(
+                matches!(err, ParseError::InvalidTransferEncoding { .. }),
+                "{:?}",
+                req
+            );
+        }
+
+        let err =
+            HttpRequest::parse("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel")
+                .unwrap_err();
+        assert_eq!(err, ParseError::TruncatedChunk { offset: 53 });
+    }
+
+    #[test]
+    fn test_binary_body() {
+        let mut req =
+            b"POST /upload HTTP/1.1\r\nContent-Type: image/png\r\nContent-Length: 6\r\n\r\n"
+                .to_vec();
+        req.extend_from_slice(&[0x89, b'P', b'N', b'G', 0x00, 0xff]);
+        let http_req = HttpRequest::try_from(req.as_slice()).unwrap();
+        assert_eq!(http_req.bytes(), &[0x89, b'P', b'N', b'G', 0x00, 0xff]);
+        assert_eq!(
+            http_req.text(),
+            Err(TextError::InvalidUtf8 { valid_up_to: 0 })
+        );
+
+        let mut req = b"GET /\xff HTTP/1.1\r\n\r\n".to_vec();
+        assert_eq!(
+            HttpRequest::parse(&req).unwrap_err(),
+            ParseError::MalformedRequestLine { offset: 0 }
+        );
+        req = b"GET / HTTP/1.1\r\nX-Bin: \xff\r\n\r\n".to_vec();
+        assert_eq!(
+            HttpRequest::parse(&req).unwrap_err(),
+            ParseError::BadHeaderLine { offset: 16 }
+        );
+    }
+
+    #[test]
+    fn test_text_uses_declared_charset() {
+        let req = b"POST / HTTP/1.1\r\nContent-Type: text/plain; charset=ISO-8859-1\r\nContent-Length: 4\r\n\r\ncaf\xe9";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(http_req.text().unwrap(), "café");
+
+        let req = b"POST / HTTP/1.1\r\nContent-Type: text/plain; charset=\"us-ascii\"\r\nContent-Length: 4\r\n\r\ncaf\xe9";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(http_req.text(), Err(TextError::NotAscii { offset: 3 }));
+
+        let req = "POST / HTTP/1.1\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\n\r\ncafé";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(http_req.text().unwrap(), "café");
+
+        let req = "POST / HTTP/1.1\r\nContent-Type: text/plain; charset=koi8-r\r\nContent-Length: 1\r\n\r\nx";
+        let http_req = HttpRequest::parse(req).unwrap();
+        assert_eq!(
+            http_req.text(),
+            Err(TextError::UnsupportedCharset("koi8-r".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_head_without_empty_line_is_incomplete() {
+        let err = HttpRequest::parse("GET / HTTP/1.1\r\nHost: localhost\r\n").unwrap_err();
+        assert_eq!(err, ParseError::IncompleteHead { offset: 33 });
+    }
+
+    #[test]
+    fn test_truncated_body() {
+        let req = "POST /submit HTTP/1.1\r\nContent-Length: 20\r\n\r\nshort";
+        let err = HttpRequest::parse(req).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::TruncatedBody {
+                expected: 20,
+                received: 5
+            }
+        );
+    }
+
+    fn request(method: Method, target: &str, version: Version) -> HttpRequest {
+        HttpRequest {
+            method,
+            version,
+            resource: target.parse().unwrap(),
+            headers: HeaderMap::new(),
+            msg_body: Vec::new(),
+            trailers: HeaderMap::new(),
+            must_close: false,
+        }
+    }
+
+    #[test]
+    fn test_write_to_adds_host_and_framing() {
+        let mut req = request(Method::Post, "/a?b=1", Version::V1_1);
+        req.headers.insert("Content-Length", "99").unwrap();
+        req.headers.insert("Accept", "*/*").unwrap();
+        req.headers.insert("Transfer-Encoding", "gzip").unwrap();
+        req.msg_body = b"hi".to_vec();
+        assert_eq!(
+            Vec::try_from(req).unwrap(),
+            b"POST /a?b=1 HTTP/1.1\r\nHost: \r\nAccept: */*\r\nContent-Length: 2\r\n\r\nhi"
+        );
+
+        let req = request(Method::Get, "http://example.com:8080/x", Version::V1_1);
+        assert_eq!(
+            Vec::try_from(req).unwrap(),
+            b"GET http://example.com:8080/x HTTP/1.1\r\nHost: example.com:8080\r\n\r\n"
+        );
+
+        // an explicit Host is kept where it was, and a bodiless PUT still says so
+        let mut req = request(Method::Put, "/f", Version::V1_0);
+        req.headers.insert("Accept", "*/*").unwrap();
+        req.headers.insert("Host", "h").unwrap();
+        assert_eq!(
+            Vec::try_from(req).unwrap(),
+            b"PUT /f HTTP/1.0\r\nAccept: */*\r\nHost: h\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_write_to_sends_trailers_after_a_chunked_body() {
+        let mut req = request(Method::Post, "/up", Version::V1_1);
+        req.headers.insert("Host", "h").unwrap();
+        req.msg_body = b"hello".to_vec();
+        req.trailers.insert("Digest", "sha-256=abc").unwrap();
+        let mut out = Vec::new();
+        req.write_to(&mut out).unwrap();
+        assert_eq!(
+            out,
+            b"POST /up HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\nDigest: sha-256=abc\r\n\r\n"
+        );
+
+        // HTTP/1.0 has no chunked coding to carry them, and dropping them
+        // would lose data
+        req.version = Version::V1_0;
+        let err = Vec::try_from(req).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_write_to_refuses_request_lines_that_would_not_parse() {
+        let mut smuggled = request(Method::Get, "/", Version::V1_1);
+        smuggled.resource = Resource::Path {
+            path: "/a HTTP/1.1\r\nX-Injected: 1\r\n".to_string(),
+            query: None,
+        };
+        let mut asterisk = request(Method::Get, "/", Version::V1_1);
+        asterisk.resource = Resource::Asterisk;
+        let mut method = request(Method::Extension("GET /".to_string()), "/", Version::V1_1);
+        method.version = Version::V2_0;
+
+        for req in [smuggled, asterisk, method] {
+            let err = req.write_to(&mut Vec::new()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            let err = Vec::try_from(req).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    // xorshift64, so the round trip covers many shapes without a dependency
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+            &items[self.below(items.len())]
+        }
+
+        fn text(&mut self, alphabet: &[u8], max_len: usize) -> String {
+            let len = self.below(max_len + 1);
+            (0..len).map(|_| *self.pick(alphabet) as char).collect()
+        }
+    }
+
+    // a request as a well-behaved peer would send it: Host first and framing
+    // that matches the body
+    fn random_request(rng: &mut Rng) -> HttpRequest {
+        const PCHARS: &[u8] = b"abcxyz0129-._~!$&'()*+,;=:@";
+        const VALUE: &[u8] = b"abc XYZ 019 !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+        let method = rng
+            .pick(&[
+                Method::Get,
+                Method::Head,
+                Method::Post,
+                Method::Put,
+                Method::Delete,
+                Method::Options,
+                Method::Patch,
+                Method::Connect,
+                Method::Extension("PROPFIND".to_string()),
+            ])
+            .clone();
+        let version = *rng.pick(&[Version::V1_0, Version::V1_1]);
+        let authority = format!("host{}.example:{}", rng.below(10), 8000 + rng.below(100));
+        let mut path = String::new();
+        for _ in 0..rng.below(4) {
+            path.push('/');
+            path.push_str(&rng.text(PCHARS, 6));
+        }
+        let query = (rng.below(2) == 0).then(|| rng.text(b"ab=&019", 8));
+        let resource = match (&method, rng.below(3)) {
+            (Method::Connect, _) => Resource::Authority(authority.clone()),
+            (Method::Options, 0) => Resource::Asterisk,
+            (_, 0) => Resource::Absolute {
+                scheme: "http".to_string(),
+                authority: authority.clone(),
+                path,
+                query,
+            },
+            _ => Resource::Path {
+                path: if path.is_empty() {
+                    "/".to_string()
+                } else {
+                    path
+                },
+                query,
+            },
+        };
+
+        let mut req = HttpRequest {
+            method,
+            version,
+            resource,
+            headers: HeaderMap::new(),
+            msg_body: Vec::new(),
+            trailers: HeaderMap::new(),
+            must_close: false,
+        };
+        req.headers.append("Host", &authority).unwrap();
+        for _ in 0..rng.below(5) {
+            let name = *rng.pick(&["Accept", "x-trace", "Cookie", "User-Agent", "X-Multi"]);
+            let value = rng.text(VALUE, 12);
+            req.headers.append(name, value.trim()).unwrap();
+        }
+        req.msg_body = (0..rng.below(40)).map(|_| rng.next() as u8).collect();
+        if version == Version::V1_1 && rng.below(3) == 0 {
+            for _ in 0..=rng.below(2) {
+                let value = rng.text(VALUE, 12);
+                req.trailers.append("X-Checksum", value.trim()).unwrap();
+            }
+            req.headers.append("Transfer-Encoding", "chunked").unwrap();
+        } else if !req.msg_body.is_empty()
+            || matches!(req.method, Method::Post | Method::Put | Method::Patch)
+        {
+            let length = req.msg_body.len().to_string();
+            req.headers.append("Content-Length", &length).unwrap();
+        }
+        req
+    }
+
+    #[test]
+    fn test_serialized_requests_parse_back_unchanged() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..1000 {
+            let req = random_request(&mut rng);
+            let bytes = Vec::try_from(req.clone()).unwrap();
+            assert_eq!(
+                HttpRequest::parse(&bytes).as_ref(),
+                Ok(&req),
+                "{:?}",
+                String::from_utf8_lossy(&bytes)
+            );
+        }
     }
 }
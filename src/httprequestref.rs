@@ -0,0 +1,366 @@
+use std::borrow::Cow;
+
+use crate::chunked;
+use crate::error::{ParseError, TextError};
+use crate::headermap::HeaderMap;
+use crate::httprequest::{
+    FramingCheck, HttpRequest, Method, RequestLine, Resource, Version, decode_text,
+    is_preface_line, keep_alive,
+};
+use crate::parser::{Framing, HeadScanner, Line, ParserConfig, unfold};
+
+/// A request parsed in place: the method, target, headers and body are slices
+/// of the input buffer.
+///
+/// Parsing validates the whole message exactly as [`HttpRequest::parse`] does
+/// but allocates nothing unless a header is folded over several lines or the
+/// body is chunked. Header lookups scan the head again instead of building a
+/// map, which is cheaper when a handler only reads a few fields. Use
+/// [`HttpRequestRef::into_owned`] to keep the request beyond the buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequestRef<'buf> {
+    buf: &'buf [u8],
+    line: RequestLine<'buf>,
+    // the header fields lie between `head_start` and `head_len`
+    head_start: usize,
+    head_len: usize,
+    framing: Framing,
+    // both Transfer-Encoding and Content-Length were sent
+    conflicting: bool,
+    body: Cow<'buf, [u8]>,
+    trailers: HeaderMap,
+    consumed: usize,
+}
+
+impl<'buf> HttpRequestRef<'buf> {
+    pub fn parse(buf: &'buf [u8]) -> Result<Self, ParseError> {
+        Self::parse_with(buf, ParserConfig::default())
+    }
+
+    /// Parses a request, refusing it as soon as any of the limits in `config`
+    /// is exceeded.
+    pub fn parse_with(buf: &'buf [u8], config: ParserConfig) -> Result<Self, ParseError> {
+        let mut scanner = RequestScanner::new(config);
+        let mut line = None;
+        let mut head_start = 0;
+        let mut version = Version::V1_1;
+        let mut check = FramingCheck::new(config.max_body);
+        // a field is checked once no folded line can continue it
+        let mut pending: Option<Field<'buf>> = None;
+        loop {
+            let Some((next, offset)) = scanner.next(buf)? else {
+                return Err(ParseError::IncompleteHead { offset: buf.len() });
+            };
+            let complete = match next {
+                Line::Start(first) => {
+                    if is_preface_line(first) {
+                        return Err(ParseError::Http2Preface);
+                    }
+                    let request_line = RequestLine::parse(first, offset)?;
+                    version = request_line.version;
+                    line = Some(request_line);
+                    head_start = scanner.pos();
+                    continue;
+                }
+                Line::Field(name, value) => pending.replace((name, Cow::Borrowed(value), offset)),
+                Line::Fold(continuation) => {
+                    if let Some((_, value, _)) = pending.as_mut() {
+                        unfold(value.to_mut(), continuation);
+                    }
+                    continue;
+                }
+                Line::End => pending.take(),
+            };
+            if let Some((name, value, offset)) = complete {
+                check.field(name, &value, version, offset)?;
+            }
+            if next == Line::End {
+                break;
+            }
+        }
+        let head_len = scanner.pos();
+        let Some(line) = line else {
+            return Err(ParseError::IncompleteHead { offset: head_len });
+        };
+
+        let framing = check.framing();
+        let (body, trailers, consumed) = match framing {
+            Framing::Chunked => {
+                let decoded = chunked::decode_limited(&buf[head_len..], head_len, config)?;
+                let consumed = head_len + decoded.consumed;
+                (Cow::Owned(decoded.body), decoded.trailers, consumed)
+            }
+            Framing::Length(expected) => {
+                let received = buf.len() - head_len;
+                if received < expected {
+                    return Err(ParseError::TruncatedBody { expected, received });
+                }
+                let consumed = head_len + expected;
+                let body = Cow::Borrowed(&buf[head_len..consumed]);
+                (body, HeaderMap::new(), consumed)
+            }
+        };
+
+        Ok(Self {
+            buf,
+            line,
+            head_start,
+            head_len,
+            framing,
+            conflicting: check.is_conflicting(),
+            body,
+            trailers,
+            consumed,
+        })
+    }
+
+    /// The method token exactly as sent; see [`HttpRequestRef::into_owned`]
+    /// for a [`Method`].
+    pub fn method(&self) -> &'buf str {
+        self.line.method
+    }
+
+    /// The raw request-target.
+    pub fn target(&self) -> &'buf str {
+        self.line.target
+    }
+
+    /// The raw path of the target, as [`Resource::path`] would return it.
+    pub fn path(&self) -> &'buf str {
+        self.line.form.split(self.line.target).0
+    }
+
+    pub fn query(&self) -> Option<&'buf str> {
+        self.line.form.split(self.line.target).1
+    }
+
+    pub fn version(&self) -> Version {
+        self.line.version
+    }
+
+    /// The first value of the header `name`, compared ignoring ASCII case.
+    pub fn header(&self, name: &str) -> Option<Cow<'buf, str>> {
+        self.header_all(name).next()
+    }
+
+    pub fn header_all<'a>(&self, name: &'a str) -> impl Iterator<Item = Cow<'buf, str>> + 'a
+    where
+        'buf: 'a,
+    {
+        self.headers()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// The header fields in the order they were sent. A value is borrowed
+    /// unless it was folded over several lines.
+    pub fn headers(&self) -> impl Iterator<Item = (&'buf str, Cow<'buf, str>)> + use<'buf> {
+        let chunked = self.framing == Framing::Chunked;
+        let fields = Fields {
+            buf: &self.buf[..self.head_len],
+            scanner: RequestScanner::fields_at(self.head_start),
+            pending: None,
+        };
+        fields
+            // Content-Length is dropped in favour of the transfer coding,
+            // as it is for an owned request
+            .filter(move |(name, _, _)| !(chunked && name.eq_ignore_ascii_case("Content-Length")))
+            .map(|(name, value, _)| (name, value))
+    }
+
+    /// The body, borrowed from the input unless a transfer coding had to be removed.
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn text(&self) -> Result<Cow<'_, str>, TextError> {
+        let content_type = self.header("Content-Type");
+        decode_text(&self.body, content_type.as_deref())
+    }
+
+    pub fn trailers(&self) -> &HeaderMap {
+        &self.trailers
+    }
+
+    /// Whether the connection should stay open after this request; never
+    /// after one that sent both `Transfer-Encoding` and `Content-Length`.
+    pub fn keep_alive(&self) -> bool {
+        if self.conflicting {
+            return false;
+        }
+        let connection = self.header("Connection");
+        keep_alive(connection.as_deref(), self.line.version)
+    }
+
+    /// Bytes of the input taken up by this request; a pipelined request may follow.
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Copies the request out of the buffer.
+    pub fn into_owned(self) -> HttpRequest {
+        // the method is a valid token, so only the unknown ones allocate
+        let method = self
+            .line
+            .method
+            .parse()
+            .unwrap_or_else(|_| Method::Extension(self.line.method.to_string()));
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers() {
+            // every field was validated by `parse`
+            let _ = headers.append(name, &value);
+        }
+        HttpRequest {
+            method,
+            version: self.line.version,
+            resource: Resource::from_form(self.line.target, self.line.form),
+            headers,
+            msg_body: self.body.into_owned(),
+            trailers: self.trailers,
+            must_close: self.conflicting,
+        }
+    }
+}
+
+impl<'buf> TryFrom<&'buf [u8]> for HttpRequestRef<'buf> {
+    type Error = ParseError;
+
+    fn try_from(buf: &'buf [u8]) -> Result<Self, Self::Error> {
+        HttpRequestRef::parse(buf)
+    }
+}
+
+impl From<HttpRequestRef<'_>> for HttpRequest {
+    fn from(req: HttpRequestRef<'_>) -> Self {
+        req.into_owned()
+    }
+}
+
+// name, value and the offset of the field line
+type Field<'buf> = (&'buf str, Cow<'buf, str>, usize);
+
+type RequestScanner = HeadScanner<(Method, Resource, Version)>;
+
+// The header fields of a validated head, with folded lines joined.
+#[derive(Debug)]
+struct Fields<'buf> {
+    buf: &'buf [u8],
+    scanner: RequestScanner,
+    pending: Option<Field<'buf>>,
+}
+
+impl<'buf> Iterator for Fields<'buf> {
+    type Item = Field<'buf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the head was validated by `parse`, so scanning cannot fail
+        while let Ok(Some((line, offset))) = self.scanner.next(self.buf) {
+            match line {
+                Line::Field(name, value) => {
+                    let field = (name, Cow::Borrowed(value), offset);
+                    if let Some(previous) = self.pending.replace(field) {
+                        return Some(previous);
+                    }
+                }
+                Line::Fold(continuation) => {
+                    if let Some((_, value, _)) = self.pending.as_mut() {
+                        unfold(value.to_mut(), continuation);
+                    }
+                }
+                Line::Start(_) | Line::End => break,
+            }
+        }
+        self.pending.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::httprequest::HTTP2_PREFACE;
+
+    #[test]
+    fn test_borrows_from_the_buffer() {
+        let buf = b"POST /upload?id=7 HTTP/1.1\r\nHost: localhost:8080\r\nContent-Length: 5\r\n\r\nhelloGET";
+        let req = HttpRequestRef::parse(buf).unwrap();
+        assert_eq!(req.method(), "POST");
+        assert_eq!(req.target(), "/upload?id=7");
+        assert_eq!(req.path(), "/upload");
+        assert_eq!(req.query(), Some("id=7"));
+        assert_eq!(req.version(), Version::V1_1);
+        assert!(matches!(
+            req.header("host"),
+            Some(Cow::Borrowed("localhost:8080"))
+        ));
+        assert!(matches!(req.body, Cow::Borrowed(b"hello")));
+        assert_eq!(req.consumed(), buf.len() - 3);
+        assert!(req.keep_alive());
+    }
+
+    #[test]
+    fn test_folded_and_repeated_headers() {
+        let buf = b"GET / HTTP/1.1\r\nX-Long: a\r\n  b\r\nAccept: text/html\r\naccept: text/plain\r\n\r\n";
+        let req = HttpRequestRef::parse(buf).unwrap();
+        assert!(matches!(req.header("x-long"), Some(Cow::Owned(v)) if v == "a b"));
+        assert_eq!(
+            req.header_all("ACCEPT").collect::<Vec<_>>(),
+            vec!["text/html", "text/plain"]
+        );
+        assert_eq!(req.headers().count(), 3);
+        assert_eq!(req.header("Missing"), None);
+    }
+
+    #[test]
+    fn test_chunked_body_and_absolute_target() {
+        let buf = b"POST http://example.com HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\nX-Sum: 3\r\n\r\n";
+        let req = HttpRequestRef::parse(buf).unwrap();
+        assert_eq!(req.path(), "/");
+        assert_eq!(req.bytes(), b"abc");
+        assert_eq!(req.trailers().get("x-sum"), Some("3"));
+        assert_eq!(req.header("Content-Length"), None);
+        assert_eq!(req.consumed(), buf.len());
+    }
+
+    #[test]
+    fn test_into_owned_matches_owned_parse() {
+        let inputs: [&[u8]; 6] = [
+            b"GET /a/b?x=1 HTTP/1.1\r\nHost: h\r\n\r\n",
+            b"PURGE /cache HTTP/1.0\r\n\r\n",
+            b"CONNECT example.com:443 HTTP/1.1\r\n\r\n",
+            b"POST /p HTTP/1.1\nContent-Type: text/plain; charset=latin1\nContent-Length: 2\n\n\xe9!",
+            b"PUT /f HTTP/1.1\r\nX-Fold: one\r\n\ttwo\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nz\r\n0\r\nT: v\r\n\r\n",
+            b"OPTIONS * HTTP/1.1\r\nSet-Cookie: a=1\r\nset-cookie: b=2\r\n\r\n",
+        ];
+        for input in inputs {
+            let owned = HttpRequest::parse(input).unwrap();
+            let borrowed = HttpRequest::from(HttpRequestRef::parse(input).unwrap());
+            assert_eq!(borrowed, owned);
+        }
+    }
+
+    #[test]
+    fn test_errors_match_owned_parse() {
+        let inputs: [&[u8]; 12] = [
+            b"GET / HTTP/1.1\r\nHost: x",
+            b"GET / HTTP/1.1",
+            HTTP2_PREFACE.as_bytes(),
+            b"GET  / HTTP/1.1\r\n\r\n",
+            b"GET / HTTP/3\r\n\r\n",
+            b"GET example.com:80 HTTP/1.1\r\n\r\n",
+            b"GET / HTTP/1.1\r\nBad Header: x\r\n\r\n",
+            b"GET / HTTP/1.1\r\n folded: first\r\n\r\n",
+            b"GET / HTTP/1.1\r\nX: a\r\n \x01\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+            b"POST / HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort",
+        ];
+        for input in inputs {
+            assert_eq!(
+                HttpRequestRef::parse(input).unwrap_err(),
+                HttpRequest::parse(input).unwrap_err(),
+                "{:?}",
+                String::from_utf8_lossy(input)
+            );
+        }
+    }
+}
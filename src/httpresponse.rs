@@ -1,120 +1,669 @@
 use std::collections::HashMap;
-use std::io::{Result, Write};
+use std::fmt;
+use std::io::{self, IoSlice, Write};
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct HttpResponse<'a> {
-    version: &'a str,
-    status_code: &'a str,
-    status_text: &'a str,
-    headers: Option<HashMap<&'a str, &'a str>>,
-    body: Option<String>,
+use crate::body::{Body, TrailerSource};
+use crate::chunked::{self, ChunkedWriter, DEFAULT_CHUNK_SIZE};
+use crate::error::{InvalidHeader, ParseError};
+use crate::headermap::{HeaderMap, is_field_value};
+use crate::httprequest::{self, Method, Version};
+use crate::parser::{self, ParserConfig, ResponseParser, Status};
+use crate::statuscode::StatusCode;
+
+#[derive(Debug, PartialEq)]
+pub struct HttpResponse {
+    version: Version,
+    status: StatusCode,
+    // replaces the canonical reason phrase when set
+    reason: Option<String>,
+    headers: HeaderMap,
+    body: Body,
+    chunk_size: usize,
+    trailers: Trailers,
+    // a parsed response after which the connection must close: its body ran
+    // until the close, or it sent both Transfer-Encoding and Content-Length
+    // (RFC 9112, section 6.1)
+    must_close: bool,
 }
 
-impl<'a> Default for HttpResponse<'a> {
+impl Default for HttpResponse {
     fn default() -> Self {
         Self {
-            version: "HTTP/1.1",
-            status_code: "200",
-            status_text: "OK",
-            headers: None,
-            body: None,
+            version: Version::V1_1,
+            status: StatusCode::OK,
+            reason: None,
+            headers: HeaderMap::new(),
+            body: Body::Empty,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            trailers: Trailers::None,
+            must_close: false,
+        }
+    }
+}
+
+// How the end of the body is marked on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    Length(u64),
+    Chunked,
+    /// The body runs until the connection closes, for HTTP/1.0 peers that
+    /// cannot decode chunks.
+    Close,
+    /// The status rules out a body, so the head is all that is sent.
+    Bodiless,
+}
+
+// Trailer fields, either received with a parsed response or produced by a
+// source as the body is sent; a source can neither be printed nor compared.
+#[derive(Default)]
+enum Trailers {
+    #[default]
+    None,
+    Fields(HeaderMap),
+    Source(Box<dyn TrailerSource>),
+}
+
+impl fmt::Debug for Trailers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trailers::None => write!(f, "None"),
+            Trailers::Fields(fields) => f.debug_tuple("Fields").field(fields).finish(),
+            Trailers::Source(_) => write!(f, "Source(..)"),
+        }
+    }
+}
+
+impl PartialEq for Trailers {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Trailers::None, Trailers::None) => true,
+            (Trailers::Fields(a), Trailers::Fields(b)) => a == b,
+            _ => false,
         }
     }
 }
 
-impl<'a> HttpResponse<'a> {
+impl HttpResponse {
+    /// `status_code` must be three digits from 100 to 599: debug builds
+    /// panic on anything else, while release builds send a 500 rather than
+    /// fail mid-response. Check an untrusted code with `StatusCode::from_str`
+    /// first, or pass a [`StatusCode`] to [`HttpResponse::builder`]. Likewise
+    /// a header that is not valid field syntax panics in debug builds and is
+    /// dropped in release builds; the builder reports it as an error instead.
+    /// `headers` are sent sorted by name; the builder also lets you choose
+    /// the order.
     pub fn new(
-        status_code: &'a str,
-        headers: Option<HashMap<&'a str, &'a str>>,
+        status_code: &str,
+        headers: Option<HashMap<&str, &str>>,
         body: Option<String>,
     ) -> Self {
-        let mut response: HttpResponse<'a> = HttpResponse::default();
-        
-        response.status_code = status_code;
-        
-        response.headers = match &headers {
-            Some(_h) => headers,
+        let status = status_code.parse();
+        debug_assert!(status.is_ok(), "invalid status code {:?}", status_code);
+        let mut response = HttpResponse {
+            status: status.unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            ..HttpResponse::default()
+        };
+
+        // a field that is not valid header syntax is a caller bug like an
+        // invalid status; release builds drop it rather than send it
+        match headers {
+            Some(h) => {
+                // a HashMap iterates in a different order on every run; sorting
+                // keeps the serialized head stable for caches and golden files
+                let mut fields: Vec<_> = h.into_iter().collect();
+                fields.sort_by_cached_key(|(k, _)| k.to_ascii_lowercase());
+                for (k, v) in fields {
+                    let appended = response.headers.append(k, v);
+                    debug_assert!(appended.is_ok(), "invalid header {:?}: {:?}", k, v);
+                }
+            }
             None => {
-                let mut h = HashMap::new();
-                h.insert("Content-Type", "text/html");
-                Some(h)
+                let _ = response.headers.insert("Content-Type", "text/html");
             }
+        }
+
+        response.body = body.map_or(Body::Empty, Body::from);
+        response
+    }
+
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::default()
+    }
+
+    /// `200 OK` with an empty body.
+    pub fn ok() -> Self {
+        Self::default()
+    }
+
+    /// `404 Not Found` with an empty body.
+    pub fn not_found() -> Self {
+        Self::with_status(StatusCode::NOT_FOUND)
+    }
+
+    /// `302 Found` pointing at `location`, which fails if it is not a valid
+    /// header value.
+    pub fn redirect(location: &str) -> Result<Self, InvalidHeader> {
+        Self::builder()
+            .status(StatusCode::FOUND)
+            .header("Location", location)
+            .build()
+    }
+
+    pub fn with_status(status: StatusCode) -> Self {
+        Self {
+            status,
+            ..Self::default()
+        }
+    }
+
+    /// Parses a complete response to a `method` request, which decides
+    /// whether it has a body: responses to HEAD never do, whatever their
+    /// `Content-Length` says, and neither do 1xx, 204 and 304 responses.
+    /// A body without `Content-Length` or a transfer coding is everything
+    /// after the head, since it ends when the connection closes.
+    pub fn parse(buf: &[u8], method: &Method) -> Result<Self, ParseError> {
+        Self::parse_with(buf, method, ParserConfig::default())
+    }
+
+    /// Parses a response, refusing it as soon as any of the limits in
+    /// `config` is exceeded.
+    pub fn parse_with(
+        buf: &[u8],
+        method: &Method,
+        config: ParserConfig,
+    ) -> Result<Self, ParseError> {
+        let mut parser = ResponseParser::with_config(method, config);
+        let Status::Complete(head_len) = parser.parse(buf)? else {
+            return Err(ParseError::IncompleteHead { offset: buf.len() });
         };
-        
-        response.status_text = match response.status_code {
-            "200" => "OK",
-            "400" => "Bad Request",
-            "404" => "Not Found",
-            "500" => "Internal Server Error",
-            _ => "Not Found",
+        let (mut response, framing) = parser.into_parts()?;
+
+        let body = &buf[head_len..];
+        response.body = Body::Bytes(match framing {
+            Some(parser::Framing::Chunked) => {
+                let decoded = chunked::decode_limited(body, head_len, config)?;
+                response.set_received_trailers(decoded.trailers);
+                decoded.body
+            }
+            Some(parser::Framing::Length(expected)) => {
+                if body.len() < expected {
+                    return Err(ParseError::TruncatedBody {
+                        expected,
+                        received: body.len(),
+                    });
+                }
+                body[..expected].to_vec()
+            }
+            None if body.len() > config.max_body => {
+                return Err(ParseError::BodyTooLarge {
+                    offset: head_len + config.max_body,
+                });
+            }
+            None => body.to_vec(),
+        });
+        Ok(response)
+    }
+
+    // a response with the parsed status line and fields and an empty body
+    pub(crate) fn from_head(line: StatusLine, headers: HeaderMap, must_close: bool) -> Self {
+        // the canonical phrase is implied, so a parsed `200 OK` equals `ok()`
+        let reason =
+            Some(line.reason).filter(|r| Some(r.as_str()) != line.status.canonical_reason());
+        Self {
+            version: line.version,
+            status: line.status,
+            reason,
+            headers,
+            must_close,
+            ..Self::default()
+        }
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Answers with the version the request was made with, e.g. `HTTP/1.0`
+    /// for an old client, instead of the `HTTP/1.1` default. HTTP/2 has no
+    /// text syntax, so [`HttpResponse::write_to`] refuses it.
+    pub fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: StatusCode) {
+        self.status = status;
+    }
+
+    /// The reason phrase sent in the status line.
+    pub fn reason(&self) -> &str {
+        self.status_text()
+    }
+
+    /// Sends `reason` in the status line instead of the canonical phrase.
+    /// Control characters are refused, since a CR or LF would let the phrase
+    /// inject header lines.
+    pub fn set_reason(&mut self, reason: impl Into<String>) -> Result<(), InvalidHeader> {
+        let reason = reason.into();
+        if !is_field_value(&reason) {
+            return Err(InvalidHeader::Reason(reason));
+        }
+        self.reason = Some(reason);
+        Ok(())
+    }
+
+    pub fn set_body(&mut self, body: impl Into<Body>) {
+        self.body = body.into();
+    }
+
+    /// Largest chunk written when the body is sent chunked.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size;
+    }
+
+    /// Sends the fields produced by `source` after the body. This makes an
+    /// HTTP/1.1 response chunked even when its length is known; HTTP/1.0
+    /// has no trailers, so they are dropped there.
+    pub fn set_trailers(&mut self, source: impl TrailerSource + 'static) {
+        self.trailers = Trailers::Source(Box::new(source));
+    }
+
+    // keeps the trailers of a body that was read separately from the head
+    pub(crate) fn set_received_trailers(&mut self, trailers: HeaderMap) {
+        self.trailers = if trailers.is_empty() {
+            Trailers::None
+        } else {
+            Trailers::Fields(trailers)
         };
-        
-        response.body = body;
-        response
     }
 
-    pub fn send_response(&self, write_stream: &mut impl Write) -> Result<()> {
-        let response_string = String::from(self.clone());
-        write!(write_stream, "{}", response_string)?;
+    /// The trailer fields that followed the chunked body of a parsed response.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        match &self.trailers {
+            Trailers::Fields(fields) => Some(fields),
+            Trailers::None | Trailers::Source(_) => None,
+        }
+    }
+
+    /// Whether the connection stays open after this response is sent, or
+    /// after it was received for a parsed one.
+    pub fn keep_alive(&self) -> bool {
+        if self.must_close {
+            return false;
+        }
+        if let Ok(Framing::Close) = self.framing() {
+            return false;
+        }
+        httprequest::keep_alive(self.headers.get("Connection"), self.version)
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    pub fn send_response(&mut self, write_stream: &mut impl Write) -> io::Result<()> {
+        self.write_to(write_stream)
+    }
+
+    /// Writes the response to `w`. Headers go out in insertion order, followed
+    /// by `Content-Length` when the body's length is known up front and by
+    /// `Transfer-Encoding: chunked` otherwise; an HTTP/1.0 response of unknown
+    /// length is sent with `Connection: close` and ends when the peer sees
+    /// the connection close. A 1xx, 204 or 304 response is sent as its head
+    /// alone, without a framing header or the body.
+    ///
+    /// These framing headers belong to the serializer: any `Content-Length` or
+    /// `Transfer-Encoding` set by the caller is left out, since a second,
+    /// conflicting one would let a peer split the response.
+    ///
+    /// The head is assembled in a small buffer; an in-memory body is handed
+    /// over together with it in one vectored write so it is never copied,
+    /// while a streamed body is copied through as it is read.
+    ///
+    /// An `HTTP/2.0` response is refused with `InvalidInput`, since no peer
+    /// could read it in HTTP/1 syntax.
+    pub fn write_to<W: Write + ?Sized>(&mut self, w: &mut W) -> io::Result<()> {
+        if self.version == Version::V2_0 {
+            let err = ParseError::UnsupportedVersion { offset: 0 };
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, err));
+        }
+        let framing = self.framing()?;
+        let mut head = Vec::with_capacity(self.head_len());
+        self.write_head(&mut head, framing);
+        match (framing, self.body.as_bytes()) {
+            (Framing::Length(_), Some(bytes)) => {
+                write_all_vectored(w, &mut [IoSlice::new(&head), IoSlice::new(bytes)])
+            }
+            (Framing::Length(len), None) => {
+                w.write_all(&head)?;
+                self.body.copy_to(w, Some(len))
+            }
+            (Framing::Chunked, _) => {
+                w.write_all(&head)?;
+                self.write_chunked(w)
+            }
+            (Framing::Close, _) => {
+                w.write_all(&head)?;
+                self.body.copy_to(w, None)
+            }
+            (Framing::Bodiless, _) => w.write_all(&head),
+        }
+    }
+
+    /// Appends the serialized response to `buf`, for callers that batch
+    /// several responses into one write.
+    pub fn serialize_into(&mut self, buf: &mut Vec<u8>) -> io::Result<()> {
+        let body_len = self.body.as_bytes().map_or(0, <[u8]>::len);
+        buf.reserve(self.head_len() + body_len);
+        self.write_to(buf)
+    }
+
+    fn framing(&self) -> io::Result<Framing> {
+        // 1xx, 204 and 304 responses end with their head, and a framing
+        // header would have the peer read the next response as their body
+        // (RFC 9110, section 8.6; RFC 9112, section 6.3)
+        if self.status.is_informational()
+            || self.status == StatusCode::NO_CONTENT
+            || self.status == StatusCode::NOT_MODIFIED
+        {
+            return Ok(Framing::Bodiless);
+        }
+        let len = self.body.len()?;
+        Ok(match len {
+            _ if !self.version.supports_chunked() => len.map_or(Framing::Close, Framing::Length),
+            Some(len) if matches!(self.trailers, Trailers::None) => Framing::Length(len),
+            _ => Framing::Chunked,
+        })
+    }
+
+    fn write_chunked<W: Write + ?Sized>(&mut self, w: &mut W) -> io::Result<()> {
+        let mut chunked = ChunkedWriter::with_chunk_size(w, self.chunk_size);
+        match &mut self.trailers {
+            Trailers::Source(source) => {
+                let mut observed = Observe {
+                    inner: &mut chunked,
+                    source: source.as_mut(),
+                };
+                self.body.copy_to(&mut observed, None)?;
+                chunked.finish_with_trailers(&source.trailers())?;
+            }
+            Trailers::Fields(fields) => {
+                self.body.copy_to(&mut chunked, None)?;
+                chunked.finish_with_trailers(fields)?;
+            }
+            Trailers::None => {
+                self.body.copy_to(&mut chunked, None)?;
+                chunked.finish()?;
+            }
+        }
         Ok(())
     }
 
-    fn version(&self) -> &str {
-        self.version
+    // status line, headers, the framing header and the empty line
+    fn write_head(&self, buf: &mut Vec<u8>, framing: Framing) {
+        buf.extend_from_slice(self.version.as_str().as_bytes());
+        buf.push(b' ');
+        push_decimal(buf, self.status.as_u16().into());
+        buf.push(b' ');
+        buf.extend_from_slice(self.status_text().as_bytes());
+        buf.extend_from_slice(b"\r\n");
+        // our own `Connection: close` must not be contradicted
+        let skip = (framing == Framing::Close).then_some("Connection");
+        push_fields(buf, &self.headers, skip);
+        match framing {
+            Framing::Length(len) => push_content_length(buf, len),
+            Framing::Chunked => buf.extend_from_slice(b"Transfer-Encoding: chunked\r\n"),
+            Framing::Close => buf.extend_from_slice(b"Connection: close\r\n"),
+            Framing::Bodiless => {}
+        }
+        buf.extend_from_slice(b"\r\n");
     }
-    
-    fn status_code(&self) -> &str {
-        self.status_code
+
+    // an upper bound on what `write_head` produces
+    fn head_len(&self) -> usize {
+        let status_line = "HTTP/1.1 200 \r\n".len() + self.status_text().len();
+        status_line + fields_len(&self.headers)
     }
-    
+
+    // unregistered codes without a custom reason get an empty phrase,
+    // which RFC 9112 allows
     fn status_text(&self) -> &str {
-        self.status_text
-    }
-    
-    fn headers(&self) -> String {
-        match &self.headers {
-            Some(map) => {
-                let mut header_string = String::new();
-                for (k, v) in map.iter() {
-                    header_string = format!("{}{}: {}\r\n", header_string, k, v);
-                }
-                header_string
-            }
-            None => String::new(),
+        self.reason
+            .as_deref()
+            .or(self.status.canonical_reason())
+            .unwrap_or("")
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Body {
+        &mut self.body
+    }
+}
+
+/// Serializes the response in memory, failing as [`HttpResponse::write_to`]
+/// does, e.g. when a streamed body cannot be read.
+impl TryFrom<HttpResponse> for Vec<u8> {
+    type Error = io::Error;
+
+    fn try_from(mut res: HttpResponse) -> Result<Self, Self::Error> {
+        let mut buf = Vec::new();
+        res.serialize_into(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Like the conversion into `Vec<u8>`, with any body bytes that are not
+/// UTF-8 replaced.
+impl TryFrom<HttpResponse> for String {
+    type Error = io::Error;
+
+    fn try_from(res: HttpResponse) -> Result<Self, Self::Error> {
+        let buf = Vec::try_from(res)?;
+        Ok(match String::from_utf8(buf) {
+            Ok(text) => text,
+            Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+        })
+    }
+}
+
+// passes writes through while showing them to a trailer source
+struct Observe<'a, W> {
+    inner: W,
+    source: &'a mut dyn TrailerSource,
+}
+
+impl<W: Write> Write for Observe<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.source.observe(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Appends the field lines of a head, leaving out `Content-Length` and
+// `Transfer-Encoding`, which the serializers derive from the body themselves,
+// and `skip` if given.
+pub(crate) fn push_fields(buf: &mut Vec<u8>, headers: &HeaderMap, skip: Option<&str>) {
+    for (name, value) in headers {
+        let owned = name.eq_ignore_ascii_case("Content-Length")
+            || name.eq_ignore_ascii_case("Transfer-Encoding")
+            || skip.is_some_and(|skip| name.eq_ignore_ascii_case(skip));
+        if owned {
+            continue;
+        }
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(value.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+pub(crate) fn push_content_length(buf: &mut Vec<u8>, len: u64) {
+    buf.extend_from_slice(b"Content-Length: ");
+    push_decimal(buf, len);
+    buf.extend_from_slice(b"\r\n");
+}
+
+// An upper bound on what `push_fields`, one framing field and the empty line
+// add to a head, so that its buffer never reallocates.
+pub(crate) fn fields_len(headers: &HeaderMap) -> usize {
+    let fields: usize = headers.iter().map(|(n, v)| n.len() + v.len() + 4).sum();
+    // the longest framing field is a Content-Length of 20 digits
+    fields + "Content-Length: \r\n\r\n".len() + 20
+}
+
+pub(crate) fn push_decimal(buf: &mut Vec<u8>, n: u64) {
+    let mut digits = [0; 20];
+    let mut i = digits.len();
+    let mut n = n;
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
         }
     }
-    
-    pub fn body(&self) -> &str {
-        match &self.body {
-            Some(b) => b.as_str(),
-            None => "",
+    buf.extend_from_slice(&digits[i..]);
+}
+
+// `Write::write_all_vectored` is still unstable
+pub(crate) fn write_all_vectored<W: Write + ?Sized>(
+    w: &mut W,
+    mut bufs: &mut [IoSlice<'_>],
+) -> io::Result<()> {
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match w.write_vectored(bufs) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
         }
     }
+    Ok(())
 }
 
-impl<'a> From<HttpResponse<'a>> for String {
-    fn from(res: HttpResponse<'a>) -> Self {
-        let body_length = match &res.body {
-            Some(b) => b.len(),
-            None => 0,
+/// The parts of a status line, as the response parser reads them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StatusLine {
+    pub(crate) version: Version,
+    pub(crate) status: StatusCode,
+    pub(crate) reason: String,
+}
+
+impl StatusLine {
+    pub(crate) fn parse(s: &str, offset: usize) -> Result<Self, ParseError> {
+        let (version, rest) = s
+            .split_once(' ')
+            .ok_or(ParseError::MalformedStatusLine { offset })?;
+        let version = match version.parse() {
+            Ok(Version::V2_0) | Err(_) => return Err(ParseError::UnsupportedVersion { offset }),
+            Ok(version) => version,
         };
-        
-        format!(
-            "{} {} {}\r\n{}Content-Length: {}\r\n\r\n{}",
-            res.version(),
-            res.status_code(),
-            res.status_text(),
-            res.headers(),
-            body_length,
-            res.body()
-        )
+        // the space before an empty reason phrase is often left out
+        let code_offset = offset + "HTTP/1.1 ".len();
+        let (code, reason) = rest.split_once(' ').unwrap_or((rest, ""));
+        let status = code.parse().map_err(|_| ParseError::MalformedStatusLine {
+            offset: code_offset,
+        })?;
+        if !is_field_value(reason) {
+            return Err(ParseError::MalformedStatusLine {
+                offset: code_offset + code.len() + 1,
+            });
+        }
+        Ok(Self {
+            version,
+            status,
+            reason: reason.to_string(),
+        })
+    }
+}
+
+/// Builds an [`HttpResponse`] step by step; created by [`HttpResponse::builder`].
+///
+/// The first invalid header is remembered and returned from [`ResponseBuilder::build`]
+/// or [`ResponseBuilder::body`], so the chain itself never has to be unwrapped.
+#[derive(Debug, Default)]
+pub struct ResponseBuilder {
+    response: HttpResponse,
+    error: Option<InvalidHeader>,
+}
+
+impl ResponseBuilder {
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.response.status = status;
+        self
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        if let Err(err) = self.response.set_reason(reason)
+            && self.error.is_none()
+        {
+            self.error = Some(err);
+        }
+        self
+    }
+
+    pub fn version(mut self, version: Version) -> Self {
+        self.response.version = version;
+        self
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.response.chunk_size = chunk_size;
+        self
+    }
+
+    /// See [`HttpResponse::set_trailers`].
+    pub fn trailers(mut self, source: impl TrailerSource + 'static) -> Self {
+        self.response.set_trailers(source);
+        self
+    }
+
+    /// Adds a header, keeping any earlier values of the same name.
+    pub fn header(mut self, name: &str, value: impl AsRef<str>) -> Self {
+        if self.error.is_none()
+            && let Err(err) = self.response.headers.append(name, value.as_ref())
+        {
+            self.error = Some(err);
+        }
+        self
+    }
+
+    /// Finishes the response with `body`.
+    pub fn body(mut self, body: impl Into<Body>) -> Result<HttpResponse, InvalidHeader> {
+        self.response.body = body.into();
+        self.build()
+    }
+
+    /// Finishes a response without a body.
+    pub fn build(self) -> Result<HttpResponse, InvalidHeader> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.response),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
     fn test_response_struct_creation_200() {
         let response_actual = HttpResponse::new(
@@ -123,19 +672,20 @@ mod tests {
             Some("Items was testing fine as of 1st August 2025".to_string()),
         );
         let response_expected = HttpResponse {
-            version: "HTTP/1.1",
-            status_code: "200",
-            status_text: "OK",
+            version: Version::V1_1,
+            status: StatusCode::OK,
+            reason: None,
             headers: {
-                let mut h = HashMap::new();
-                h.insert("Content-Type", "text/html");
-                Some(h)
+                let mut h = HeaderMap::new();
+                h.insert("Content-Type", "text/html").unwrap();
+                h
             },
-            body: Some("Items was testing fine as of 1st August 2025".to_string()),
+            body: Body::from("Items was testing fine as of 1st August 2025"),
+            ..HttpResponse::default()
         };
         assert_eq!(response_actual, response_expected);
     }
-    
+
     #[test]
     fn test_response_struct_creation_404() {
         let response_actual = HttpResponse::new(
@@ -144,15 +694,16 @@ mod tests {
             Some("Item was shipped on 21st Dec 2020".to_string()),
         );
         let response_expected = HttpResponse {
-            version: "HTTP/1.1",
-            status_code: "404",
-            status_text: "Not Found",
+            version: Version::V1_1,
+            status: StatusCode::NOT_FOUND,
+            reason: None,
             headers: {
-                let mut h = HashMap::new();
-                h.insert("Content-Type", "text/html");
-                Some(h)
+                let mut h = HeaderMap::new();
+                h.insert("Content-Type", "text/html").unwrap();
+                h
             },
-            body: Some("Item was shipped on 21st Dec 2020".to_string()),
+            body: Body::from("Item was shipped on 21st Dec 2020"),
+            ..HttpResponse::default()
         };
         assert_eq!(response_actual, response_expected);
     }
@@ -160,45 +711,652 @@ mod tests {
     #[test]
     fn test_http_response_creation() {
         let response_expected = HttpResponse {
-            version: "HTTP/1.1",
-            status_code: "404",
-            status_text: "Not Found",
+            version: Version::V1_1,
+            status: StatusCode::NOT_FOUND,
+            reason: None,
             headers: {
-                let mut h = HashMap::new();
-                h.insert("Content-Type", "text/html");
-                Some(h)
+                let mut h = HeaderMap::new();
+                h.insert("Content-Type", "text/html").unwrap();
+                h
             },
-            body: Some("Item was shipped on 21st Dec 2020".to_string()),
+            body: Body::from("Item was shipped on 21st Dec 2020"),
+            ..HttpResponse::default()
         };
-        let http_string: String = response_expected.into();
+        let http_string = String::try_from(response_expected).unwrap();
         let response_actual = "HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\nContent-Length: 33\r\n\r\nItem was shipped on 21st Dec 2020";
         assert_eq!(http_string, response_actual);
     }
-    
+
     #[test]
     fn test_response_with_no_body() {
         let response = HttpResponse::new("200", None, None);
-        let http_string: String = response.into();
+        let http_string = String::try_from(response).unwrap();
         let expected = "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 0\r\n\r\n";
         assert_eq!(http_string, expected);
     }
-    
+
     #[test]
     fn test_custom_headers() {
         let mut custom_headers = HashMap::new();
         custom_headers.insert("Content-Type", "application/json");
         custom_headers.insert("Cache-Control", "no-cache");
-        
+
         let body_content = "{\"message\": \"success\"}";
-        let response = HttpResponse::new(
-            "200",
-            Some(custom_headers),
-            Some(body_content.to_string()),
-        );
-        
-        let http_string: String = response.into();
+        let response =
+            HttpResponse::new("200", Some(custom_headers), Some(body_content.to_string()));
+
+        let http_string = String::try_from(response).unwrap();
         assert!(http_string.contains("Content-Type: application/json"));
         assert!(http_string.contains("Cache-Control: no-cache"));
         assert!(http_string.contains(&format!("Content-Length: {}", body_content.len())));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_response_echoes_request_version() {
+        let mut response = HttpResponse::new("200", None, None);
+        assert!(response.keep_alive());
+
+        response.set_version(Version::V1_0);
+        assert!(!response.keep_alive());
+        let http_string = String::try_from(response).unwrap();
+        assert!(http_string.starts_with("HTTP/1.0 200 OK\r\n"));
+
+        let mut response = HttpResponse::builder()
+            .version(Version::V2_0)
+            .build()
+            .unwrap();
+        let err = response.write_to(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            Vec::try_from(response).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_response_headers_share_header_map() {
+        let mut response = HttpResponse::new("200", None, None);
+        response.headers_mut().append("Set-Cookie", "a=1").unwrap();
+        response.headers_mut().append("Set-Cookie", "b=2").unwrap();
+        assert_eq!(response.headers().get("content-type"), Some("text/html"));
+
+        let http_string = String::try_from(response).unwrap();
+        assert!(http_string.contains("Set-Cookie: a=1\r\nSet-Cookie: b=2\r\n"));
+    }
+
+    #[test]
+    fn test_every_status_gets_its_reason() {
+        let cases = [
+            ("201", "HTTP/1.1 201 Created\r\n"),
+            ("204", "HTTP/1.1 204 No Content\r\n"),
+            ("301", "HTTP/1.1 301 Moved Permanently\r\n"),
+            ("401", "HTTP/1.1 401 Unauthorized\r\n"),
+            ("503", "HTTP/1.1 503 Service Unavailable\r\n"),
+            ("299", "HTTP/1.1 299 \r\n"),
+        ];
+        for (code, status_line) in cases {
+            let http_string = String::try_from(HttpResponse::new(code, None, None)).unwrap();
+            assert!(http_string.starts_with(status_line), "{}", http_string);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invalid status code \"abc\"")]
+    fn test_new_asserts_on_an_invalid_status() {
+        HttpResponse::new("abc", None, None);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invalid header \"Set-Cookie\"")]
+    fn test_new_asserts_on_an_invalid_header() {
+        let headers = HashMap::from([("Set-Cookie", "id=1\r\nX-Injected: 1")]);
+        HttpResponse::new("200", Some(headers), None);
+    }
+
+    #[test]
+    fn test_bodiless_statuses_are_sent_without_framing() {
+        let cases = [
+            (StatusCode::NO_CONTENT, "HTTP/1.1 204 No Content\r\n\r\n"),
+            (
+                StatusCode::NOT_MODIFIED,
+                "HTTP/1.1 304 Not Modified\r\n\r\n",
+            ),
+            (StatusCode::CONTINUE, "HTTP/1.1 100 Continue\r\n\r\n"),
+        ];
+        for (status, expected) in cases {
+            let http_string = String::try_from(HttpResponse::with_status(status)).unwrap();
+            assert_eq!(http_string, expected);
+
+            // not even a body set by mistake, streamed or not, goes out
+            let response = HttpResponse::builder()
+                .status(status)
+                .body("stray")
+                .unwrap();
+            assert_eq!(String::try_from(response).unwrap(), expected);
+            let mut response = HttpResponse::builder()
+                .status(status)
+                .trailers(Checksum::default())
+                .body(Body::from_reader(&b"stray"[..], None))
+                .unwrap();
+            let mut sent = Vec::new();
+            response.send_response(&mut sent).unwrap();
+            assert_eq!(sent, expected.as_bytes());
+            assert!(response.keep_alive());
+        }
+
+        // so our own parser finds the next response right after the head
+        let mut sent = Vec::try_from(HttpResponse::with_status(StatusCode::NO_CONTENT)).unwrap();
+        sent.extend(Vec::try_from(HttpResponse::not_found()).unwrap());
+        let mut reader = &sent[..];
+        let (response, _) = parser::read_response(&mut reader, &Method::Get).unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let (response, _) = parser::read_response(&mut reader, &Method::Get).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_custom_reason() {
+        let mut response = HttpResponse::new("200", None, None);
+        response.set_status(StatusCode::from_u16(299).unwrap());
+        response.set_reason("Mostly Fine").unwrap();
+        assert!(response.status().is_success());
+        let http_string = String::try_from(response).unwrap();
+        assert!(http_string.starts_with("HTTP/1.1 299 Mostly Fine\r\n"));
+    }
+
+    #[test]
+    fn test_builder() {
+        let request_id = 42;
+        let response = HttpResponse::builder()
+            .status(StatusCode::CREATED)
+            .header("Content-Type", "application/json")
+            .header("X-Request-Id", format!("req-{}", request_id))
+            .body("{}")
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers().get("x-request-id"), Some("req-42"));
+
+        let http_string = String::try_from(response).unwrap();
+        assert_eq!(
+            http_string,
+            "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nX-Request-Id: req-42\r\nContent-Length: 2\r\n\r\n{}"
+        );
+    }
+
+    #[test]
+    fn test_builder_reports_the_first_invalid_header() {
+        let err = HttpResponse::builder()
+            .header("Bad Name", "x")
+            .header("X-Injected", "a\r\nb")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, InvalidHeader::Name("Bad Name".to_string()));
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        assert_eq!(HttpResponse::ok().status(), StatusCode::OK);
+        let http_string = String::try_from(HttpResponse::not_found()).unwrap();
+        assert_eq!(
+            http_string,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+        );
+
+        let response = HttpResponse::redirect("/login?next=%2F").unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers().get("Location"), Some("/login?next=%2F"));
+        assert!(HttpResponse::redirect("/\r\nSet-Cookie: x=1").is_err());
+    }
+
+    // accepts at most `limit` bytes per call and remembers where they came from
+    struct ShortWriter {
+        limit: usize,
+        written: Vec<u8>,
+        slices: Vec<*const u8>,
+    }
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            let mut n = 0;
+            for buf in bufs {
+                self.slices.push(buf.as_ptr());
+                let take = buf.len().min(self.limit - n);
+                self.written.extend_from_slice(&buf[..take]);
+                n += take;
+            }
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_to_hands_over_the_body_without_copying() {
+        let mut response = HttpResponse::builder()
+            .header("Content-Type", "text/plain")
+            .body("x".repeat(1000))
+            .unwrap();
+        let mut writer = ShortWriter {
+            limit: 7,
+            written: Vec::new(),
+            slices: Vec::new(),
+        };
+        response.write_to(&mut writer).unwrap();
+
+        // an in-memory body can be sent again
+        let mut expected = Vec::new();
+        response.serialize_into(&mut expected).unwrap();
+        assert_eq!(writer.written, expected);
+        let body = response.body().as_bytes().unwrap();
+        assert!(writer.slices.contains(&body.as_ptr()));
+    }
+
+    #[test]
+    fn test_serialize_into_appends() {
+        let mut buf = b"previous".to_vec();
+        let mut response = HttpResponse::builder()
+            .status(StatusCode::from_u16(299).unwrap())
+            .body("body")
+            .unwrap();
+        response.serialize_into(&mut buf).unwrap();
+        assert_eq!(
+            buf,
+            b"previousHTTP/1.1 299 \r\nContent-Length: 4\r\n\r\nbody"
+        );
+
+        let mut sent = Vec::new();
+        response.send_response(&mut sent).unwrap();
+        assert_eq!(sent, &buf[8..]);
+    }
+
+    #[test]
+    fn test_header_order_is_deterministic() {
+        let serialize = || {
+            // every HashMap is seeded differently, so iteration order varies
+            let headers: HashMap<&str, &str> = [
+                ("X-Trace", "1"),
+                ("Cache-Control", "no-cache"),
+                ("content-type", "text/plain"),
+                ("ETag", "\"v1\""),
+                ("Vary", "Accept"),
+                ("Age", "0"),
+            ]
+            .into_iter()
+            .collect();
+            String::try_from(HttpResponse::new(
+                "200",
+                Some(headers),
+                Some("hi".to_string()),
+            ))
+            .unwrap()
+        };
+
+        let expected = "HTTP/1.1 200 OK\r\nAge: 0\r\nCache-Control: no-cache\r\ncontent-type: text/plain\r\nETag: \"v1\"\r\nVary: Accept\r\nX-Trace: 1\r\nContent-Length: 2\r\n\r\nhi";
+        for _ in 0..50 {
+            assert_eq!(serialize(), expected);
+        }
+    }
+
+    #[test]
+    fn test_builder_keeps_insertion_order() {
+        let response = HttpResponse::builder()
+            .header("Zeta", "1")
+            .header("Alpha", "2")
+            .header("Mid", "3")
+            .build()
+            .unwrap();
+        let http_string = String::try_from(response).unwrap();
+        assert_eq!(
+            http_string,
+            "HTTP/1.1 200 OK\r\nZeta: 1\r\nAlpha: 2\r\nMid: 3\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_binary_body() {
+        let png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0xff];
+        let response = HttpResponse::builder()
+            .header("Content-Type", "image/png")
+            .body(png.clone())
+            .unwrap();
+        let bytes = Vec::try_from(response).unwrap();
+        let head = b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 10\r\n\r\n";
+        assert_eq!(&bytes[..head.len()], head);
+        assert_eq!(&bytes[head.len()..], png);
+    }
+
+    #[test]
+    fn test_file_body_uses_its_length() {
+        let path = std::env::temp_dir().join(format!("http-body-{}.txt", std::process::id()));
+        std::fs::write(&path, b"from disk").unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let mut response = HttpResponse::builder().body(file).unwrap();
+        let mut sent = Vec::new();
+        response.send_response(&mut sent).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            sent,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nfrom disk"
+        );
+    }
+
+    #[test]
+    fn test_reader_bodies_choose_framing() {
+        let mut response = HttpResponse::builder()
+            .body(Body::from_reader(&b"known"[..], Some(5)))
+            .unwrap();
+        let mut sent = Vec::new();
+        response.send_response(&mut sent).unwrap();
+        assert_eq!(sent, b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nknown");
+
+        let stream = io::Cursor::new(b"streamed without a length".to_vec());
+        let mut response = HttpResponse::builder()
+            .body(Body::from_reader(stream, None))
+            .unwrap();
+        let mut sent = Vec::new();
+        response.send_response(&mut sent).unwrap();
+        let head = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert_eq!(&sent[..head.len()], head);
+        let decoded = crate::chunked::decode(&sent[head.len()..], head.len()).unwrap();
+        assert_eq!(decoded.body, b"streamed without a length");
+
+        // a body that falls short of its length is an error, not a cut-off message
+        let response = HttpResponse::builder()
+            .body(Body::from_reader(&b"short"[..], Some(10)))
+            .unwrap();
+        assert!(Vec::try_from(response).is_err());
+    }
+
+    // a toy checksum: the byte count and the sum of all bytes
+    #[derive(Default)]
+    struct Checksum {
+        len: usize,
+        sum: u32,
+    }
+
+    impl TrailerSource for Checksum {
+        fn observe(&mut self, data: &[u8]) {
+            self.len += data.len();
+            self.sum = data
+                .iter()
+                .fold(self.sum, |sum, &b| sum.wrapping_add(b.into()));
+        }
+
+        fn trailers(&mut self) -> HeaderMap {
+            let mut trailers = HeaderMap::new();
+            trailers
+                .append("X-Checksum", &format!("{}:{}", self.len, self.sum))
+                .unwrap();
+            trailers
+        }
+    }
+
+    #[test]
+    fn test_chunked_response_with_trailers() {
+        let mut response = HttpResponse::builder()
+            .chunk_size(4)
+            .trailers(Checksum::default())
+            .body("hello world")
+            .unwrap();
+        assert!(response.keep_alive());
+        let mut sent = Vec::new();
+        response.send_response(&mut sent).unwrap();
+
+        let head = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert_eq!(&sent[..head.len()], head);
+        assert!(sent[head.len()..].starts_with(b"4\r\nhell\r\n4\r\no wo\r\n3\r\nrld\r\n0\r\n"));
+        let decoded = crate::chunked::decode(&sent[head.len()..], head.len()).unwrap();
+        assert_eq!(decoded.body, b"hello world");
+        assert_eq!(decoded.trailers.get("X-Checksum"), Some("11:1116"));
+    }
+
+    #[test]
+    fn test_http_1_0_falls_back_to_close_delimited() {
+        let mut response = HttpResponse::builder()
+            .version(Version::V1_0)
+            .header("Connection", "keep-alive")
+            .trailers(Checksum::default())
+            .body(Body::from_reader(&b"until close"[..], None))
+            .unwrap();
+        assert!(!response.keep_alive());
+        let mut sent = Vec::new();
+        response.send_response(&mut sent).unwrap();
+        assert_eq!(
+            sent,
+            b"HTTP/1.0 200 OK\r\nConnection: close\r\n\r\nuntil close"
+        );
+
+        // a known length still gets a Content-Length
+        let mut response = HttpResponse::builder()
+            .version(Version::V1_0)
+            .body(Body::from_reader(&b"sized"[..], Some(5)))
+            .unwrap();
+        let mut sent = Vec::new();
+        response.send_response(&mut sent).unwrap();
+        assert_eq!(sent, b"HTTP/1.0 200 OK\r\nContent-Length: 5\r\n\r\nsized");
+    }
+
+    #[test]
+    fn test_serializer_owns_the_framing_headers() {
+        let mut headers = HashMap::new();
+        headers.insert("Content-Length", "999");
+        headers.insert("Transfer-Encoding", "gzip, chunked");
+        headers.insert("X-Kept", "yes");
+        let response = HttpResponse::new("200", Some(headers), Some("four".to_string()));
+        let http_string = String::try_from(response).unwrap();
+        assert_eq!(
+            http_string,
+            "HTTP/1.1 200 OK\r\nX-Kept: yes\r\nContent-Length: 4\r\n\r\nfour"
+        );
+
+        let mut response = HttpResponse::builder()
+            .header("content-length", "1")
+            .body(Body::from_reader(&b"streamed"[..], None))
+            .unwrap();
+        let mut sent = Vec::new();
+        response.send_response(&mut sent).unwrap();
+        assert!(sent.starts_with(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_header_injection_is_refused() {
+        let mut response = HttpResponse::ok();
+        assert_eq!(
+            response.set_reason("OK\r\nSet-Cookie: evil=1"),
+            Err(InvalidHeader::Reason(
+                "OK\r\nSet-Cookie: evil=1".to_string()
+            ))
+        );
+        assert!(
+            response
+                .headers_mut()
+                .append("X-Evil\r\nSet-Cookie", "1")
+                .is_err()
+        );
+        assert!(response.headers_mut().append("X-Evil", "a\nb").is_err());
+        let http_string = String::try_from(response).unwrap();
+        assert_eq!(http_string, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+
+        let err = HttpResponse::builder()
+            .reason("Fine\rX-Injected: 1")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            InvalidHeader::Reason("Fine\rX-Injected: 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_what_we_serialize() {
+        let not_found = || {
+            HttpResponse::builder()
+                .status(StatusCode::NOT_FOUND)
+                .header("Content-Type", "text/plain")
+                .body("no such page")
+                .unwrap()
+        };
+        let bytes = Vec::try_from(not_found()).unwrap();
+        let received = HttpResponse::parse(&bytes, &Method::Get).unwrap();
+        assert_eq!(received.version(), Version::V1_1);
+        assert_eq!(received.status(), StatusCode::NOT_FOUND);
+        assert_eq!(received.reason(), "Not Found");
+        assert_eq!(received.headers().get("content-type"), Some("text/plain"));
+        assert_eq!(received.headers().get("content-length"), Some("12"));
+        assert_eq!(received.body().as_bytes(), Some(&b"no such page"[..]));
+
+        // and what we parse serializes the same way again
+        let mut sent = not_found();
+        sent.headers_mut().insert("Content-Length", "12").unwrap();
+        assert_eq!(received, sent);
+        assert_eq!(Vec::try_from(received).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_parse_chunked_and_close_delimited_bodies() {
+        let input = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Length: 99\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\nX-Checksum: abc\r\n\r\n";
+        let response = HttpResponse::parse(input, &Method::Get).unwrap();
+        assert_eq!(response.body().as_bytes(), Some(&b"hello world"[..]));
+        assert_eq!(response.trailers().unwrap().get("x-checksum"), Some("abc"));
+        assert!(!response.headers().contains_key("Content-Length"));
+
+        // chunked as the final coding is decoded, leaving the others to the caller
+        let input =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n3\r\ngz!\r\n0\r\n\r\n";
+        let response = HttpResponse::parse(input, &Method::Get).unwrap();
+        assert_eq!(response.body().as_bytes(), Some(&b"gz!"[..]));
+        assert_eq!(
+            response.headers().get("transfer-encoding"),
+            Some("gzip, chunked")
+        );
+        // any other final coding leaves the body to run until the connection closes
+        let input =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip\r\nContent-Length: 2\r\n\r\n3\r\ngz!";
+        let response = HttpResponse::parse(input, &Method::Get).unwrap();
+        assert_eq!(response.body().as_bytes(), Some(&b"3\r\ngz!"[..]));
+        assert!(!response.keep_alive());
+        // chunked still may not be applied twice
+        assert_eq!(
+            HttpResponse::parse(
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked, gzip\r\nTransfer-Encoding: chunked\r\n\r\n",
+                &Method::Get
+            )
+            .unwrap_err(),
+            ParseError::InvalidTransferEncoding { offset: 51 }
+        );
+
+        let close = b"HTTP/1.1 200 OK\r\n\r\nhello world";
+        let response = HttpResponse::parse(close, &Method::Get).unwrap();
+        assert_eq!(response.body().as_bytes(), Some(&b"hello world"[..]));
+        assert!(!response.keep_alive());
+
+        let input = b"HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\nuntil the end";
+        let response = HttpResponse::parse(input, &Method::Get).unwrap();
+        assert_eq!(response.version(), Version::V1_0);
+        assert_eq!(response.body().as_bytes(), Some(&b"until the end"[..]));
+        assert!(response.trailers().is_none());
+
+        let config = ParserConfig {
+            max_body: 5,
+            ..ParserConfig::default()
+        };
+        assert_eq!(
+            HttpResponse::parse_with(input, &Method::Get, config).unwrap_err(),
+            ParseError::BodyTooLarge { offset: 50 }
+        );
+        assert_eq!(
+            HttpResponse::parse(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nab",
+                &Method::Get
+            )
+            .unwrap_err(),
+            ParseError::TruncatedBody {
+                expected: 5,
+                received: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_bodiless_responses() {
+        // a HEAD response announces the length of a body it does not carry,
+        // however large that is
+        let head = b"HTTP/1.1 200 OK\r\nContent-Length: 1000000000000\r\n\r\n";
+        let response = HttpResponse::parse(head, &Method::Head).unwrap();
+        assert_eq!(response.body().as_bytes(), Some(&b""[..]));
+        assert_eq!(
+            response.headers().get("Content-Length"),
+            Some("1000000000000")
+        );
+        assert_eq!(
+            HttpResponse::parse(head, &Method::Get).unwrap_err(),
+            ParseError::BodyTooLarge { offset: 17 }
+        );
+
+        for input in [
+            b"HTTP/1.1 204 No Content\r\nContent-Length: 4\r\n\r\nnext".as_slice(),
+            b"HTTP/1.1 304 Not Modified\r\nContent-Length: 4\r\n\r\nnext",
+            b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n\r\n",
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\nframes",
+        ] {
+            let response = HttpResponse::parse(input, &Method::Get).unwrap();
+            assert_eq!(response.body().as_bytes(), Some(&b""[..]), "{:?}", input);
+        }
+
+        // a successful CONNECT leaves a tunnel rather than a body
+        let input = b"HTTP/1.1 200 Connection Established\r\n\r\ntunnelled bytes";
+        let response = HttpResponse::parse(input, &Method::Connect).unwrap();
+        assert_eq!(response.reason(), "Connection Established");
+        assert_eq!(response.body().as_bytes(), Some(&b""[..]));
+    }
+
+    #[test]
+    fn test_status_lines() {
+        let response = HttpResponse::parse(b"HTTP/1.1 299\r\n\r\n", &Method::Get).unwrap();
+        assert_eq!(response.status().as_u16(), 299);
+        assert_eq!(response.reason(), "");
+        let response = HttpResponse::parse(b"HTTP/1.1 200 Fine\r\n\r\n", &Method::Get).unwrap();
+        assert_eq!(response.reason(), "Fine");
+
+        let cases: [(&[u8], ParseError); 6] = [
+            (
+                b"HTTP/1.1\r\n\r\n",
+                ParseError::MalformedStatusLine { offset: 0 },
+            ),
+            (
+                b"HTTP/2.0 200 OK\r\n\r\n",
+                ParseError::UnsupportedVersion { offset: 0 },
+            ),
+            (
+                b"GET / HTTP/1.1\r\n\r\n",
+                ParseError::UnsupportedVersion { offset: 0 },
+            ),
+            (
+                b"HTTP/1.1 20 OK\r\n\r\n",
+                ParseError::MalformedStatusLine { offset: 9 },
+            ),
+            (
+                b"HTTP/1.1 600 Odd\r\n\r\n",
+                ParseError::MalformedStatusLine { offset: 9 },
+            ),
+            (
+                b"HTTP/1.1 200 O\x01K\r\n\r\n",
+                ParseError::MalformedStatusLine { offset: 13 },
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                HttpResponse::parse(input, &Method::Get).unwrap_err(),
+                expected
+            );
+        }
+    }
+}
@@ -1,2 +1,12 @@
+pub mod body;
+pub mod chunked;
+pub mod client;
+pub mod error;
+pub mod headermap;
 pub mod httprequest;
+pub mod httprequestref;
 pub mod httpresponse;
+pub mod parser;
+pub mod pool;
+pub mod resource;
+pub mod statuscode;
@@ -0,0 +1,1163 @@
+use std::io::{self, BufRead, Read};
+use std::marker::PhantomData;
+
+use crate::chunked::ChunkedReader;
+use crate::error::{ParseError, ReadError};
+use crate::headermap::{HeaderMap, is_field_value, parse_field_line};
+use crate::httprequest::{
+    FramingCheck, HttpRequest, MAX_HEADER_LINE, Method, Resource, Version, is_preface_line,
+};
+use crate::httpresponse::{HttpResponse, StatusLine};
+use crate::statuscode::StatusCode;
+
+/// Progress of an incremental parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status<T> {
+    /// Parsing finished; for a head this is its length including the empty line.
+    Complete(T),
+    /// More input is needed.
+    Partial,
+}
+
+/// Size limits applied while parsing, so that a peer cannot make us buffer
+/// an unbounded head or body. Every limit is checked as soon as it is crossed,
+/// before the rest of the offending line has arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserConfig {
+    /// Longest request or status line, without its line ending; a request
+    /// line that is longer is a 414.
+    pub max_request_line: usize,
+    /// Longest single header line; longer ones are a 431.
+    pub max_header_line: usize,
+    /// Most header fields, counting each folded field once; more are a 431.
+    pub max_headers: usize,
+    /// Largest header section, line endings included; larger ones are a 431.
+    pub max_header_bytes: usize,
+    /// Largest body after removing any transfer coding; larger ones are a 413.
+    pub max_body: usize,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            max_request_line: 8 * 1024,
+            max_header_line: MAX_HEADER_LINE,
+            max_headers: 100,
+            max_header_bytes: 64 * 1024,
+            max_body: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// A resumable parser for a request head.
+///
+/// Call [`RequestParser::parse`] with everything received so far each time
+/// more bytes arrive. Lines are parsed once as they complete, and the search
+/// for a line ending resumes where the previous call stopped, so no byte is
+/// looked at twice however the input is split.
+#[derive(Debug)]
+pub struct RequestParser {
+    head: HeadParser<(Method, Resource, Version)>,
+}
+
+/// A resumable parser for a response head, the client-side counterpart of
+/// [`RequestParser`].
+///
+/// Whether a response has a body depends on the request it answers, so the
+/// parser is created for that request's method.
+#[derive(Debug)]
+pub struct ResponseParser {
+    head: HeadParser<StatusLine>,
+    method: Method,
+}
+
+// The field handling shared by both parsers, generic over the start line.
+#[derive(Debug)]
+struct HeadParser<L> {
+    scanner: HeadScanner<L>,
+    start_line: Option<L>,
+    headers: HeaderMap,
+    framing: FramingCheck,
+    // the framing fields of a message that cannot have a body are not checked,
+    // e.g. the Content-Length of a HEAD response describes another response
+    bodiless: bool,
+    // the latest header is held back until we know no folded line continues it
+    pending: Option<(String, String, usize)>,
+}
+
+/// Splits a head into lines as they arrive, enforcing the limits of a
+/// [`ParserConfig`] on each one; the parsers and [`HttpRequestRef`] build on it.
+///
+/// [`HttpRequestRef`]: crate::httprequestref::HttpRequestRef
+#[derive(Debug)]
+pub(crate) struct HeadScanner<L> {
+    config: ParserConfig,
+    state: State,
+    // start of the first line that has not been scanned yet
+    pos: usize,
+    // there is no line ending between `pos` and `scanned`
+    scanned: usize,
+    // where the header section starts, once the start line is scanned
+    head_start: usize,
+    // header fields so far, counting each folded field once
+    fields: usize,
+    start_line: PhantomData<fn() -> L>,
+}
+
+/// A line of a head, without its line ending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Line<'b> {
+    Start(&'b str),
+    /// A field line split into its name and trimmed value.
+    Field(&'b str, &'b str),
+    /// An obsolete folded continuation of the previous field, trimmed.
+    Fold(&'b str),
+    /// The empty line that ends the head.
+    End,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    #[default]
+    StartLine,
+    Headers,
+    Complete,
+}
+
+/// How the body following a head is delimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Framing {
+    Length(usize),
+    Chunked,
+}
+
+// The first line of a message, which is all that differs between the heads
+// of requests and responses.
+pub(crate) trait StartLine: Sized {
+    fn parse(line: &str, offset: usize) -> Result<Self, ParseError>;
+
+    fn version(&self) -> Version;
+
+    fn malformed(offset: usize) -> ParseError;
+
+    fn too_long(offset: usize) -> ParseError;
+
+    // whether the message ends with its head whatever its fields say
+    fn bodiless(&self) -> bool {
+        false
+    }
+}
+
+impl StartLine for (Method, Resource, Version) {
+    fn parse(line: &str, offset: usize) -> Result<Self, ParseError> {
+        if is_preface_line(line) {
+            return Err(ParseError::Http2Preface);
+        }
+        HttpRequest::process_req_line(line, offset)
+    }
+
+    fn version(&self) -> Version {
+        self.2
+    }
+
+    fn malformed(offset: usize) -> ParseError {
+        ParseError::MalformedRequestLine { offset }
+    }
+
+    fn too_long(offset: usize) -> ParseError {
+        ParseError::RequestLineTooLong { offset }
+    }
+}
+
+impl StartLine for StatusLine {
+    fn parse(line: &str, offset: usize) -> Result<Self, ParseError> {
+        StatusLine::parse(line, offset)
+    }
+
+    fn version(&self) -> Version {
+        self.version
+    }
+
+    fn malformed(offset: usize) -> ParseError {
+        ParseError::MalformedStatusLine { offset }
+    }
+
+    fn too_long(offset: usize) -> ParseError {
+        ParseError::StatusLineTooLong { offset }
+    }
+
+    // RFC 9112, section 6.3
+    fn bodiless(&self) -> bool {
+        self.status.is_informational()
+            || self.status == StatusCode::NO_CONTENT
+            || self.status == StatusCode::NOT_MODIFIED
+    }
+}
+
+impl Default for RequestParser {
+    fn default() -> Self {
+        Self::with_config(ParserConfig::default())
+    }
+}
+
+impl RequestParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: ParserConfig) -> Self {
+        Self {
+            head: HeadParser::new(config, FramingCheck::new(config.max_body), false),
+        }
+    }
+
+    /// Parses the complete lines in `buf`, which must start with the bytes
+    /// passed to earlier calls; a shorter `buf` is refused with
+    /// [`ParseError::ShortenedInput`].
+    pub fn parse(&mut self, buf: &[u8]) -> Result<Status<usize>, ParseError> {
+        self.head.parse(buf)
+    }
+
+    /// The parsed head as a request with an empty body.
+    pub fn into_request(self) -> Result<HttpRequest, ParseError> {
+        self.into_parts().map(|(request, _)| request)
+    }
+
+    // the request and how its body is framed
+    pub(crate) fn into_parts(self) -> Result<(HttpRequest, Framing), ParseError> {
+        let ((method, resource, version), headers, check) = self.head.finish()?;
+        let request = HttpRequest {
+            method,
+            version,
+            resource,
+            headers,
+            msg_body: Vec::new(),
+            trailers: HeaderMap::new(),
+            must_close: check.is_conflicting(),
+        };
+        Ok((request, check.framing()))
+    }
+}
+
+impl ResponseParser {
+    /// A parser for the response to a `method` request.
+    pub fn new(method: &Method) -> Self {
+        Self::with_config(method, ParserConfig::default())
+    }
+
+    pub fn with_config(method: &Method, config: ParserConfig) -> Self {
+        Self {
+            // a HEAD response describes the body a GET would have had
+            head: HeadParser::new(
+                config,
+                FramingCheck::for_response(config.max_body),
+                *method == Method::Head,
+            ),
+            method: method.clone(),
+        }
+    }
+
+    /// Parses the complete lines in `buf`, which must start with the bytes
+    /// passed to earlier calls; a shorter `buf` is refused with
+    /// [`ParseError::ShortenedInput`].
+    pub fn parse(&mut self, buf: &[u8]) -> Result<Status<usize>, ParseError> {
+        self.head.parse(buf)
+    }
+
+    /// The parsed head as a response with an empty body.
+    pub fn into_response(self) -> Result<HttpResponse, ParseError> {
+        self.into_parts().map(|(response, _)| response)
+    }
+
+    // the response and how its body is framed, `None` meaning it runs until
+    // the connection closes
+    pub(crate) fn into_parts(self) -> Result<(HttpResponse, Option<Framing>), ParseError> {
+        let bodiless = self.head.bodiless;
+        let (line, headers, check) = self.head.finish()?;
+        // a successful CONNECT turns the connection into a tunnel
+        let tunnel = self.method == Method::Connect && line.status.is_success();
+        let framing = if bodiless || line.bodiless() || tunnel {
+            Some(Framing::Length(0))
+        } else {
+            check.response_framing()
+        };
+        // a body delimited by the close leaves nothing to reuse either
+        let must_close = check.is_conflicting() || framing.is_none();
+        let response = HttpResponse::from_head(line, headers, must_close);
+        Ok((response, framing))
+    }
+}
+
+impl<L: StartLine> HeadParser<L> {
+    fn new(config: ParserConfig, framing: FramingCheck, bodiless: bool) -> Self {
+        Self {
+            scanner: HeadScanner::new(config),
+            start_line: None,
+            headers: HeaderMap::new(),
+            framing,
+            bodiless,
+            pending: None,
+        }
+    }
+
+    fn parse(&mut self, buf: &[u8]) -> Result<Status<usize>, ParseError> {
+        while let Some((line, offset)) = self.scanner.next(buf)? {
+            match line {
+                Line::Start(line) => {
+                    let start_line = L::parse(line, offset)?;
+                    self.bodiless |= start_line.bodiless();
+                    self.start_line = Some(start_line);
+                }
+                Line::Field(name, value) => {
+                    self.flush_header()?;
+                    self.pending = Some((name.to_string(), value.to_string(), offset));
+                }
+                Line::Fold(continuation) => {
+                    if let Some((_, value, _)) = self.pending.as_mut() {
+                        unfold(value, continuation);
+                    }
+                }
+                Line::End => {
+                    self.flush_header()?;
+                    return Ok(Status::Complete(self.scanner.pos));
+                }
+            }
+        }
+        Ok(Status::Partial)
+    }
+
+    // the start line and fields of a complete head, and its framing fields
+    fn finish(self) -> Result<(L, HeaderMap, FramingCheck), ParseError> {
+        let (Some(line), true) = (self.start_line, self.scanner.is_complete()) else {
+            return Err(ParseError::IncompleteHead {
+                offset: self.scanner.pos,
+            });
+        };
+        let mut headers = self.headers;
+        // Transfer-Encoding overrides Content-Length, which must not survive
+        // to confuse anything this message is handed to; the framing check
+        // records that the connection must close (RFC 9112, section 6.1)
+        if headers.contains_key("Transfer-Encoding") {
+            headers.remove("Content-Length");
+        }
+        Ok((line, headers, self.framing))
+    }
+
+    fn flush_header(&mut self) -> Result<(), ParseError> {
+        let Some((name, value, offset)) = self.pending.take() else {
+            return Ok(());
+        };
+        if !self.bodiless {
+            let version = match &self.start_line {
+                Some(line) => line.version(),
+                None => Version::V1_1,
+            };
+            self.framing.field(&name, &value, version, offset)?;
+        }
+        self.headers
+            .append(&name, &value)
+            .map_err(|_| ParseError::BadHeaderLine { offset })
+    }
+}
+
+impl<L: StartLine> HeadScanner<L> {
+    pub(crate) fn new(config: ParserConfig) -> Self {
+        Self {
+            config,
+            state: State::StartLine,
+            pos: 0,
+            scanned: 0,
+            head_start: 0,
+            fields: 0,
+            start_line: PhantomData,
+        }
+    }
+
+    /// A scanner for the header section of an already validated head,
+    /// starting at `head_start`.
+    pub(crate) fn fields_at(head_start: usize) -> Self {
+        let unlimited = ParserConfig {
+            max_request_line: usize::MAX,
+            max_header_line: usize::MAX,
+            max_headers: usize::MAX,
+            max_header_bytes: usize::MAX,
+            max_body: usize::MAX,
+        };
+        Self {
+            state: State::Headers,
+            pos: head_start,
+            scanned: head_start,
+            head_start,
+            ..Self::new(unlimited)
+        }
+    }
+
+    /// Where the next line starts; the length of the head once it is complete.
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn is_complete(&self) -> bool {
+        self.state == State::Complete
+    }
+
+    /// The next complete line in `buf` and its offset, or `None` until more
+    /// input arrives. `buf` must start with the bytes passed to earlier calls.
+    pub(crate) fn next<'b>(
+        &mut self,
+        buf: &'b [u8],
+    ) -> Result<Option<(Line<'b>, usize)>, ParseError> {
+        if buf.len() < self.scanned {
+            return Err(ParseError::ShortenedInput {
+                offset: self.scanned,
+            });
+        }
+        loop {
+            if self.state == State::Complete {
+                return Ok(Some((Line::End, self.pos)));
+            }
+            let Some(end) = buf[self.scanned..].iter().position(|&b| b == b'\n') else {
+                self.scanned = buf.len();
+                // a line that is already too long is refused without waiting for its end
+                let partial = &buf[self.pos..];
+                let partial = partial.strip_suffix(b"\r").unwrap_or(partial);
+                self.check_limits(partial.len(), buf.len())?;
+                return Ok(None);
+            };
+            let line_end = self.scanned + end + 1;
+            let offset = self.pos;
+            let line = &buf[offset..line_end - 1];
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            self.check_limits(line.len(), line_end)?;
+            self.pos = line_end;
+            self.scanned = line_end;
+
+            // the head must be text; only the body may carry arbitrary bytes
+            let line = std::str::from_utf8(line).map_err(|_| match self.state {
+                State::StartLine => L::malformed(offset),
+                _ => ParseError::BadHeaderLine { offset },
+            })?;
+            let line = match self.state {
+                // a stray CRLF, typically after a previous body, may precede the
+                // request line (RFC 9112, section 2.2)
+                State::StartLine if line.is_empty() => continue,
+                State::StartLine => {
+                    self.state = State::Headers;
+                    self.head_start = self.pos;
+                    Line::Start(line)
+                }
+                // obsolete line folding continues the field above
+                State::Headers if line.starts_with([' ', '\t']) && self.fields > 0 => {
+                    let continuation = line.trim_matches([' ', '\t']);
+                    if !is_field_value(continuation) {
+                        return Err(ParseError::BadHeaderLine { offset });
+                    }
+                    Line::Fold(continuation)
+                }
+                // the first empty line ends the head
+                State::Headers if line.is_empty() => {
+                    self.state = State::Complete;
+                    Line::End
+                }
+                State::Headers => {
+                    if self.fields >= self.config.max_headers {
+                        return Err(ParseError::TooManyHeaders { offset });
+                    }
+                    self.fields += 1;
+                    let (name, value) = parse_field_line(line, offset)?;
+                    Line::Field(name, value)
+                }
+                State::Complete => Line::End,
+            };
+            return Ok(Some((line, offset)));
+        }
+    }
+
+    // `line_len` is the current line without its ending and `end` where
+    // the input seen so far stops
+    fn check_limits(&self, line_len: usize, end: usize) -> Result<(), ParseError> {
+        let offset = self.pos;
+        match self.state {
+            // skipped empty lines count against the start line, so a peer
+            // cannot keep us reading blank lines forever
+            State::StartLine if self.pos + line_len > self.config.max_request_line => {
+                Err(L::too_long(offset))
+            }
+            State::Headers if line_len > self.config.max_header_line => {
+                Err(ParseError::HeaderTooLarge { offset })
+            }
+            State::Headers if end - self.head_start > self.config.max_header_bytes => {
+                Err(ParseError::HeadersTooLarge { offset })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Joins an obsolete folded continuation onto `value` with a single space.
+pub(crate) fn unfold(value: &mut String, continuation: &str) {
+    value.push(' ');
+    value.push_str(continuation);
+}
+
+/// Reads a request head from `reader`, leaving the body to be streamed from
+/// the returned [`BodyReader`]. Only the bytes of the head are consumed, so
+/// a `BufReader` around a socket can be passed in directly.
+pub fn read_request<R: BufRead>(reader: R) -> Result<(HttpRequest, BodyReader<R>), ReadError> {
+    read_request_with(reader, ParserConfig::default())
+}
+
+/// [`read_request`] with explicit limits. The head is never buffered beyond
+/// them, and the body reader fails once the body exceeds `max_body`.
+pub fn read_request_with<R: BufRead>(
+    mut reader: R,
+    config: ParserConfig,
+) -> Result<(HttpRequest, BodyReader<R>), ReadError> {
+    let mut parser = RequestParser::with_config(config);
+    let head_len = read_head(&mut reader, |head| parser.parse(head))?;
+    let (request, framing) = parser.into_parts()?;
+    let body = BodyReader::new(reader, framing, head_len, config);
+    Ok((request, body))
+}
+
+/// Reads the head of the response to a `method` request from `reader`,
+/// leaving the body to be streamed from the returned [`BodyReader`].
+///
+/// An interim 1xx response is returned like any other, with an empty body,
+/// so a client waiting for the final response reads again.
+pub fn read_response<R: BufRead>(
+    reader: R,
+    method: &Method,
+) -> Result<(HttpResponse, BodyReader<R>), ReadError> {
+    read_response_with(reader, method, ParserConfig::default())
+}
+
+/// [`read_response`] with explicit limits.
+pub fn read_response_with<R: BufRead>(
+    mut reader: R,
+    method: &Method,
+    config: ParserConfig,
+) -> Result<(HttpResponse, BodyReader<R>), ReadError> {
+    let mut parser = ResponseParser::with_config(method, config);
+    let head_len = read_head(&mut reader, |head| parser.parse(head))?;
+    let (response, framing) = parser.into_parts()?;
+    let body = match framing {
+        Some(framing) => BodyReader::new(reader, framing, head_len, config),
+        None => BodyReader::until_close(reader, head_len, config.max_body),
+    };
+    Ok((response, body))
+}
+
+// Feeds `reader` to `parse` until it has a complete head, consuming only the
+// bytes of the head, and returns the head's length.
+fn read_head<R: BufRead>(
+    reader: &mut R,
+    mut parse: impl FnMut(&[u8]) -> Result<Status<usize>, ParseError>,
+) -> Result<usize, ReadError> {
+    let mut head = Vec::new();
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() && head.is_empty() {
+            return Err(ReadError::Closed);
+        }
+        if available.is_empty() {
+            return Err(ParseError::IncompleteHead { offset: head.len() }.into());
+        }
+        let received = head.len();
+        let n = available.len();
+        head.extend_from_slice(available);
+        if let Status::Complete(head_len) = parse(&head)? {
+            reader.consume(head_len - received);
+            return Ok(head_len);
+        }
+        reader.consume(n);
+    }
+}
+
+/// Streams a message body, removing any transfer coding.
+#[derive(Debug)]
+pub struct BodyReader<R> {
+    kind: BodyKind<R>,
+}
+
+#[derive(Debug)]
+enum BodyKind<R> {
+    Length {
+        inner: R,
+        expected: usize,
+        remaining: usize,
+    },
+    Chunked(ChunkedReader<R>),
+    /// A response body that ends when the connection closes.
+    Close {
+        inner: R,
+        // where the body starts, for the offset of `BodyTooLarge`
+        offset: usize,
+        received: usize,
+        max_body: usize,
+        done: bool,
+    },
+}
+
+impl<R: BufRead> BodyReader<R> {
+    pub(crate) fn new(inner: R, framing: Framing, offset: usize, config: ParserConfig) -> Self {
+        let kind = match framing {
+            Framing::Length(expected) => BodyKind::Length {
+                inner,
+                expected,
+                remaining: expected,
+            },
+            Framing::Chunked => BodyKind::Chunked(ChunkedReader::with_limit(inner, offset, config)),
+        };
+        Self { kind }
+    }
+
+    // a body without framing, which only a response can have (RFC 9112, section 6.3)
+    pub(crate) fn until_close(inner: R, offset: usize, max_body: usize) -> Self {
+        let kind = BodyKind::Close {
+            inner,
+            offset,
+            received: 0,
+            max_body,
+            done: false,
+        };
+        Self { kind }
+    }
+
+    /// Trailer fields of a chunked body, complete once the body has been read.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        match &self.kind {
+            BodyKind::Chunked(reader) => Some(reader.trailers()),
+            BodyKind::Length { .. } | BodyKind::Close { .. } => None,
+        }
+    }
+
+    /// Whether the whole body has been read, leaving `inner` at the next message.
+    pub fn is_done(&self) -> bool {
+        match &self.kind {
+            BodyKind::Length { remaining, .. } => *remaining == 0,
+            BodyKind::Chunked(reader) => reader.is_done(),
+            BodyKind::Close { done, .. } => *done,
+        }
+    }
+
+    /// Whether the body ends when the connection closes, which leaves the
+    /// connection unusable for another message.
+    pub fn is_close_delimited(&self) -> bool {
+        matches!(self.kind, BodyKind::Close { .. })
+    }
+
+    pub fn into_inner(self) -> R {
+        match self.kind {
+            BodyKind::Length { inner, .. } => inner,
+            BodyKind::Chunked(reader) => reader.into_inner(),
+            BodyKind::Close { inner, .. } => inner,
+        }
+    }
+}
+
+impl<R: BufRead> Read for BodyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.kind {
+            BodyKind::Length {
+                inner,
+                expected,
+                remaining,
+            } => {
+                if *remaining == 0 || buf.is_empty() {
+                    return Ok(0);
+                }
+                let limit = buf.len().min(*remaining);
+                let n = inner.read(&mut buf[..limit])?;
+                if n == 0 {
+                    let err = ParseError::TruncatedBody {
+                        expected: *expected,
+                        received: *expected - *remaining,
+                    };
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, err));
+                }
+                *remaining -= n;
+                Ok(n)
+            }
+            BodyKind::Chunked(reader) => reader.read(buf),
+            BodyKind::Close {
+                inner,
+                offset,
+                received,
+                max_body,
+                done,
+            } => {
+                let too_large = || {
+                    let err = ParseError::BodyTooLarge {
+                        offset: *offset + *max_body,
+                    };
+                    io::Error::new(io::ErrorKind::InvalidData, err)
+                };
+                // the error sticks, so a caller that reads on after it gets it again
+                if *received > *max_body {
+                    return Err(too_large());
+                }
+                if *done || buf.is_empty() {
+                    return Ok(0);
+                }
+                // read one byte past the limit to tell a body that is exactly
+                // `max_body` long from a longer one
+                let limit = buf.len().min((*max_body - *received).saturating_add(1));
+                let n = inner.read(&mut buf[..limit])?;
+                *received += n;
+                if *received > *max_body {
+                    return Err(too_large());
+                }
+                *done = n == 0;
+                Ok(n)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::httprequestref::HttpRequestRef;
+
+    #[test]
+    fn test_parser_resumes_across_splits() {
+        let req = b"GET /home HTTP/1.1\r\nHost: localhost\r\nX-Long: a\r\n b\r\n\r\nbody";
+        let mut parser = RequestParser::new();
+        for end in 0..req.len() - 4 {
+            assert_eq!(parser.parse(&req[..end]).unwrap(), Status::Partial);
+        }
+        assert_eq!(parser.parse(req).unwrap(), Status::Complete(req.len() - 4));
+
+        let http_req = parser.into_request().unwrap();
+        assert_eq!(http_req.method, Method::Get);
+        assert_eq!(http_req.headers.get("host"), Some("localhost"));
+        assert_eq!(http_req.headers.get("x-long"), Some("a b"));
+    }
+
+    #[test]
+    fn test_parser_reports_errors_as_soon_as_a_line_completes() {
+        let mut parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"GET / HTTP/1.1\r\nBad").unwrap(),
+            Status::Partial
+        );
+        assert_eq!(
+            parser.parse(b"GET / HTTP/1.1\r\nBad Header: x\r\n"),
+            Err(ParseError::BadHeaderLine { offset: 16 })
+        );
+
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.into_request().unwrap_err(),
+            ParseError::IncompleteHead { offset: 0 }
+        );
+    }
+
+    #[test]
+    fn test_parser_refuses_a_shorter_buffer() {
+        let mut parser = RequestParser::new();
+        assert_eq!(parser.parse(b"GET / HT").unwrap(), Status::Partial);
+        let err = parser.parse(b"GET").unwrap_err();
+        assert_eq!(err, ParseError::ShortenedInput { offset: 8 });
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let mut parser = ResponseParser::new(&Method::Get);
+        assert_eq!(
+            parser.parse(b"HTTP/1.1 200 OK\r\n").unwrap(),
+            Status::Partial
+        );
+        assert!(parser.parse(b"").is_err());
+    }
+
+    #[test]
+    fn test_read_request_streams_length_body() {
+        let input: &[u8] =
+            b"POST /a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET /b HTTP/1.1\r\n\r\n";
+        let mut reader = io::BufReader::with_capacity(3, input);
+
+        let (http_req, mut body) = read_request(&mut reader).unwrap();
+        assert_eq!(http_req.resource.path(), "/a");
+        assert!(http_req.msg_body.is_empty());
+        let mut content = String::new();
+        body.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "hello");
+        assert!(body.is_done());
+
+        let (http_req, mut body) = read_request(&mut reader).unwrap();
+        assert_eq!(http_req.resource.path(), "/b");
+        assert_eq!(body.read(&mut [0; 8]).unwrap(), 0);
+
+        assert!(matches!(read_request(&mut reader), Err(ReadError::Closed)));
+    }
+
+    #[test]
+    fn test_read_request_streams_chunked_body() {
+        let input: &[u8] = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\nX-Sum: 5\r\n\r\n";
+        let (_, mut body) = read_request(io::BufReader::with_capacity(4, input)).unwrap();
+        let mut content = Vec::new();
+        body.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"abcde");
+        assert_eq!(body.trailers().unwrap().get("X-Sum"), Some("5"));
+    }
+
+    #[test]
+    fn test_read_request_truncation() {
+        let input: &[u8] = b"GET / HTTP/1.1\r\nHost: x";
+        assert!(matches!(
+            read_request(input),
+            Err(ReadError::Parse(ParseError::IncompleteHead { offset: 23 }))
+        ));
+
+        let input: &[u8] = b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort";
+        let (_, mut body) = read_request(input).unwrap();
+        let err = body.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    fn small_config() -> ParserConfig {
+        ParserConfig {
+            max_request_line: 32,
+            max_header_line: 30,
+            max_headers: 3,
+            max_header_bytes: 64,
+            max_body: 10,
+        }
+    }
+
+    #[test]
+    fn test_limits_map_to_distinct_errors() {
+        let long_target = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(40));
+        let long_header = format!("GET / HTTP/1.1\r\nX-Big: {}\r\n\r\n", "a".repeat(30));
+        let many_headers = "GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\n\r\n";
+        let big_section = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-Header: value-1234\r\n".repeat(3)
+        );
+        let big_body = "POST / HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world";
+        let big_chunks = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n";
+
+        let cases = [
+            (
+                long_target.as_str(),
+                ParseError::RequestLineTooLong { offset: 0 },
+                414,
+            ),
+            (
+                long_header.as_str(),
+                ParseError::HeaderTooLarge { offset: 16 },
+                431,
+            ),
+            (many_headers, ParseError::TooManyHeaders { offset: 34 }, 431),
+            (
+                big_section.as_str(),
+                ParseError::HeadersTooLarge { offset: 60 },
+                431,
+            ),
+            (big_body, ParseError::BodyTooLarge { offset: 17 }, 413),
+            (big_chunks, ParseError::BodyTooLarge { offset: 58 }, 413),
+        ];
+        for (input, expected, status) in cases {
+            let err = HttpRequest::parse_with(input, small_config()).unwrap_err();
+            assert_eq!(err, expected, "{:?}", input);
+            assert_eq!(err.status().as_u16(), status);
+            let err = HttpRequestRef::parse_with(input.as_bytes(), small_config()).unwrap_err();
+            assert_eq!(err, expected, "{:?}", input);
+        }
+
+        // the defaults leave ordinary requests alone
+        HttpRequest::parse(&big_section).unwrap();
+        HttpRequest::parse(big_chunks).unwrap();
+    }
+
+    #[test]
+    fn test_trailers_are_held_to_the_header_limits() {
+        let chunked = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n";
+        let flood = format!("{}{}\r\n", chunked, "X-Flood: 1\r\n".repeat(10_000));
+        let long_lines = format!("{}{}\r\n", chunked, "X-Trailer: value-12345\r\n".repeat(3));
+        let long_line = format!("{}X-Big: {}\r\n\r\n", chunked, "a".repeat(30));
+
+        let cases = [
+            (flood, ParseError::TooManyHeaders { offset: 86 }),
+            (long_lines, ParseError::HeadersTooLarge { offset: 98 }),
+            (long_line, ParseError::HeaderTooLarge { offset: 50 }),
+        ];
+        for (input, expected) in cases {
+            let err = HttpRequest::parse_with(&input, small_config()).unwrap_err();
+            assert_eq!(err, expected);
+            assert_eq!(err.status().as_u16(), 431);
+            let err = HttpRequestRef::parse_with(input.as_bytes(), small_config()).unwrap_err();
+            assert_eq!(err, expected);
+
+            let (_, mut body) = read_request_with(input.as_bytes(), small_config()).unwrap();
+            let err = body.read_to_end(&mut Vec::new()).unwrap_err();
+            let err = err.into_inner().unwrap().downcast::<ParseError>().unwrap();
+            assert_eq!(*err, expected);
+        }
+    }
+
+    #[test]
+    fn test_oversized_lines_are_refused_before_they_end() {
+        let mut parser = RequestParser::with_config(small_config());
+        let line = format!("GET /{}", "a".repeat(40));
+        assert_eq!(
+            parser.parse(line.as_bytes()),
+            Err(ParseError::RequestLineTooLong { offset: 0 })
+        );
+
+        // a peer trickling an endless header never gets it buffered
+        let head = [b"GET / HTTP/1.1\r\nX: ".as_slice(), &[b'a'; 1 << 20]].concat();
+        let mut reader = io::BufReader::with_capacity(16, &head[..]);
+        let err = read_request_with(&mut reader, small_config()).unwrap_err();
+        assert!(matches!(
+            err,
+            ReadError::Parse(ParseError::HeaderTooLarge { offset: 16 })
+        ));
+    }
+
+    #[test]
+    fn test_streamed_chunked_body_is_limited() {
+        let input: &[u8] = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n8\r\nabcdefgh\r\n8\r\nabcdefgh\r\n0\r\n\r\n";
+        let (_, mut body) = read_request_with(input, small_config()).unwrap();
+        let err = body.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    // Parses `input` with the one-shot, borrowed and streaming parsers, the
+    // last fed a byte at a time, and checks that they all agree.
+    fn parse_everywhere(input: &[u8]) -> Result<HttpRequest, ParseError> {
+        let owned = HttpRequest::parse(input);
+        let borrowed = HttpRequestRef::parse(input).map(HttpRequest::from);
+        assert_eq!(borrowed, owned, "{:?}", String::from_utf8_lossy(input));
+
+        let streamed =
+            read_request(io::BufReader::with_capacity(1, input)).and_then(|(mut req, mut body)| {
+                body.read_to_end(&mut req.msg_body)?;
+                Ok(req)
+            });
+        match (&owned, streamed) {
+            (Ok(owned), Ok(streamed)) => {
+                assert_eq!(streamed.method, owned.method);
+                assert_eq!(streamed.resource, owned.resource);
+                assert_eq!(streamed.headers, owned.headers);
+                assert_eq!(streamed.msg_body, owned.msg_body);
+            }
+            (Err(owned), Err(ReadError::Parse(streamed))) => assert_eq!(&streamed, owned),
+            (owned, streamed) => panic!("{:?} but streamed {:?}", owned, streamed),
+        }
+        owned
+    }
+
+    #[test]
+    fn test_leading_empty_lines_are_skipped() {
+        for input in [
+            b"\r\nGET /a HTTP/1.1\r\n\r\n".as_slice(),
+            b"\r\n\r\nGET /a HTTP/1.1\r\n\r\n",
+            b"\n\r\nGET /a HTTP/1.1\r\n\r\n",
+        ] {
+            let req = parse_everywhere(input).unwrap();
+            assert_eq!(req.resource.path(), "/a");
+        }
+
+        // offsets still point into the original input
+        assert_eq!(
+            parse_everywhere(b"\r\n\r\nGET / HTTP/9\r\n\r\n").unwrap_err(),
+            ParseError::UnsupportedVersion { offset: 10 }
+        );
+        assert_eq!(
+            parse_everywhere(b"\r\n\r\n").unwrap_err(),
+            ParseError::IncompleteHead { offset: 4 }
+        );
+
+        // but only as many as the request line itself could have used
+        let padded = format!("{}GET / HTTP/1.1\r\n\r\n", "\r\n".repeat(10));
+        let err = HttpRequest::parse_with(&padded, small_config()).unwrap_err();
+        assert_eq!(err, ParseError::RequestLineTooLong { offset: 20 });
+        let err = HttpRequestRef::parse_with(padded.as_bytes(), small_config()).unwrap_err();
+        assert_eq!(err, ParseError::RequestLineTooLong { offset: 20 });
+        let blank = "\r\n".repeat(1 << 16);
+        let mut parser = RequestParser::with_config(small_config());
+        assert_eq!(
+            parser.parse(blank.as_bytes()),
+            Err(ParseError::RequestLineTooLong { offset: 34 })
+        );
+    }
+
+    #[test]
+    fn test_stray_crlf_between_pipelined_requests() {
+        let input: &[u8] =
+            b"POST /a HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi\r\nGET /b HTTP/1.1\r\n\r\n";
+        let mut reader = io::BufReader::with_capacity(4, input);
+        let (_, mut body) = read_request(&mut reader).unwrap();
+        body.read_to_end(&mut Vec::new()).unwrap();
+        let (http_req, _) = read_request(&mut reader).unwrap();
+        assert_eq!(http_req.resource.path(), "/b");
+    }
+
+    #[test]
+    fn test_conformance_only_the_first_line_is_the_request_line() {
+        // "HTTP" in a field value, a body or a target is just data
+        let req = parse_everywhere(
+            b"GET /docs/HTTP-guide HTTP/1.1\r\nReferer: http://example.com/HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(req.resource.path(), "/docs/HTTP-guide");
+        assert_eq!(
+            req.headers.get("referer"),
+            Some("http://example.com/HTTP/1.1")
+        );
+
+        let req = parse_everywhere(
+            b"GET / HTTP/1.1\r\nX-Note: POST /admin HTTP/1.1\r\nUser-Agent: HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(req.method, Method::Get);
+        assert_eq!(req.resource.path(), "/");
+        assert_eq!(req.headers.get("x-note"), Some("POST /admin HTTP/1.1"));
+
+        let req = parse_everywhere(
+            b"POST /upload HTTP/1.1\r\nContent-Length: 24\r\n\r\nDELETE /all HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(req.method, Method::Post);
+        assert_eq!(req.resource.path(), "/upload");
+        assert_eq!(req.msg_body, b"DELETE /all HTTP/1.1\r\n\r\n");
+
+        // a folded continuation belongs to the field above it
+        let req =
+            parse_everywhere(b"GET /a HTTP/1.1\r\nX-Fold: one\r\n DELETE /b HTTP/1.1\r\n\r\n")
+                .unwrap();
+        assert_eq!(req.resource.path(), "/a");
+        assert_eq!(req.headers.get("x-fold"), Some("one DELETE /b HTTP/1.1"));
+
+        // anything after the head of a request without a body is not part of it
+        let req =
+            parse_everywhere(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\nHost: b\r\n\r\n").unwrap();
+        assert_eq!(req.resource.path(), "/a");
+        assert!(req.headers.is_empty());
+        assert!(req.msg_body.is_empty());
+    }
+
+    #[test]
+    fn test_conformance_misplaced_request_lines_are_errors() {
+        let cases: [(&[u8], ParseError); 8] = [
+            // a request line among the fields is not a field
+            (
+                b"GET / HTTP/1.1\r\nDELETE /all HTTP/1.1\r\n\r\n",
+                ParseError::BadHeaderLine { offset: 16 },
+            ),
+            (
+                b"GET / HTTP/1.1\r\nHTTP/1.1 200 OK\r\n\r\n",
+                ParseError::BadHeaderLine { offset: 16 },
+            ),
+            // the first line is the request line even when a later one looks better
+            (
+                b"Host: example.com\r\nGET / HTTP/1.1\r\n\r\n",
+                ParseError::MalformedRequestLine { offset: 0 },
+            ),
+            (
+                b"hello HTTP/1.1 world\r\nGET / HTTP/1.1\r\n\r\n",
+                ParseError::InvalidTarget { offset: 6 },
+            ),
+            // leading whitespace is not an empty line
+            (
+                b" GET / HTTP/1.1\r\n\r\n",
+                ParseError::MalformedRequestLine { offset: 0 },
+            ),
+            (
+                b"\r\n \r\nGET / HTTP/1.1\r\n\r\n",
+                ParseError::MalformedRequestLine { offset: 2 },
+            ),
+            // nor is a lone CR
+            (
+                b"\rGET / HTTP/1.1\r\n\r\n",
+                ParseError::InvalidMethod { offset: 0 },
+            ),
+            (
+                b"GET / http/1.1\r\n\r\n",
+                ParseError::UnsupportedVersion { offset: 6 },
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                parse_everywhere(input).unwrap_err(),
+                expected,
+                "{:?}",
+                String::from_utf8_lossy(input)
+            );
+        }
+    }
+
+    #[test]
+    fn test_response_parser_resumes_across_splits() {
+        let res = b"HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\nX-Long: a\r\n b\r\n\r\ngone";
+        let mut parser = ResponseParser::new(&Method::Get);
+        for end in 0..res.len() - 4 {
+            assert_eq!(parser.parse(&res[..end]).unwrap(), Status::Partial);
+        }
+        assert_eq!(parser.parse(res).unwrap(), Status::Complete(res.len() - 4));
+
+        let response = parser.into_response().unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers().get("x-long"), Some("a b"));
+
+        let mut parser = ResponseParser::with_config(&Method::Get, small_config());
+        assert_eq!(
+            parser.parse(format!("HTTP/1.1 200 {}", "K".repeat(40)).as_bytes()),
+            Err(ParseError::StatusLineTooLong { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn test_read_response_after_interim_responses() {
+        let input: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\nX-Sum: 1\r\n\r\nHTTP/1.1 204 No Content\r\n\r\n";
+        let mut reader = io::BufReader::with_capacity(5, input);
+
+        let (response, mut body) = read_response(&mut reader, &Method::Post).unwrap();
+        assert_eq!(response.status(), StatusCode::CONTINUE);
+        assert!(body.is_done());
+        assert_eq!(body.read(&mut [0; 8]).unwrap(), 0);
+
+        let (response, mut body) = read_response(&mut reader, &Method::Post).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut content = String::new();
+        body.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "ok");
+        assert_eq!(body.trailers().unwrap().get("x-sum"), Some("1"));
+        assert!(!body.is_close_delimited());
+
+        let (response, _) = read_response(&mut reader, &Method::Post).unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(matches!(
+            read_response(&mut reader, &Method::Post),
+            Err(ReadError::Closed)
+        ));
+    }
+
+    #[test]
+    fn test_read_close_delimited_response() {
+        let input: &[u8] = b"HTTP/1.1 200 OK\r\n\r\neverything until EOF";
+        let (response, mut body) = read_response(input, &Method::Get).unwrap();
+        assert!(body.is_close_delimited());
+        assert!(!response.keep_alive());
+        let mut content = String::new();
+        body.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "everything until EOF");
+        assert!(body.is_done());
+
+        let (_, mut body) = read_response_with(input, &Method::Get, small_config()).unwrap();
+        let err = body.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // reading on after the error gets it again
+        let err = body.read(&mut [0; 4]).unwrap_err();
+        assert_eq!(
+            err.into_inner().unwrap().downcast::<ParseError>().unwrap(),
+            Box::new(ParseError::BodyTooLarge { offset: 29 })
+        );
+
+        // exactly `max_body` bytes are fine
+        let input: &[u8] = b"HTTP/1.0 200 OK\r\n\r\n0123456789";
+        let (_, mut body) = read_response_with(input, &Method::Get, small_config()).unwrap();
+        body.read_to_end(&mut Vec::new()).unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::TcpStream;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::error::ClientError;
+use crate::headermap::HeaderMap;
+
+/// Limits for a [`Pool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Most connections to one host, in use and idle together. A request
+    /// beyond it waits for one of them to be returned.
+    pub max_per_host: usize,
+    /// How long an idle connection is kept; a shorter `Keep-Alive: timeout`
+    /// from the server takes precedence.
+    pub idle_timeout: Duration,
+    /// Longest wait for a connection when a host is at its cap; `None` waits
+    /// as long as it takes.
+    pub wait_timeout: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_per_host: 8,
+            idle_timeout: Duration::from_secs(90),
+            wait_timeout: None,
+        }
+    }
+}
+
+/// Counters describing a [`Pool`], as returned by [`Pool::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Connections currently in use.
+    pub active: usize,
+    /// Idle connections waiting to be reused.
+    pub idle: usize,
+    /// Connections opened since the pool was created.
+    pub opened: u64,
+    /// Times an idle connection was reused instead of opening one.
+    pub reused: u64,
+    /// Idle connections dropped because they expired or the server closed them.
+    pub evicted: u64,
+}
+
+/// Idle HTTP/1.1 connections kept per host for reuse by a
+/// [`Client`](crate::client::Client), which shares its pool with its clones.
+pub struct Pool {
+    config: PoolConfig,
+    state: Mutex<State>,
+    // signalled whenever a connection is returned or closed
+    returned: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    hosts: HashMap<String, Host>,
+    opened: u64,
+    reused: u64,
+    evicted: u64,
+}
+
+#[derive(Default)]
+struct Host {
+    // most recently returned last, so the warmest connection is reused first
+    idle: Vec<Idle>,
+    active: usize,
+}
+
+struct Idle {
+    stream: TcpStream,
+    expires: Instant,
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Self::new(PoolConfig::default())
+    }
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("config", &self.config)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl Pool {
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::default()),
+            returned: Condvar::new(),
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let state = self.lock();
+        PoolStats {
+            active: state.hosts.values().map(|h| h.active).sum(),
+            idle: state.hosts.values().map(|h| h.idle.len()).sum(),
+            opened: state.opened,
+            reused: state.reused,
+            evicted: state.evicted,
+        }
+    }
+
+    /// Takes a live idle connection to `host`, or opens one with `connect`
+    /// if the host is below its cap, waiting for a connection to be returned
+    /// otherwise.
+    pub(crate) fn checkout(
+        &self,
+        host: &str,
+        connect: impl FnOnce() -> Result<TcpStream, ClientError>,
+    ) -> Result<Pooled<'_>, ClientError> {
+        let key = host.to_ascii_lowercase();
+        let deadline = self.config.wait_timeout.map(|t| Instant::now() + t);
+        let mut state = self.lock();
+        loop {
+            state.evict(Instant::now());
+            let State {
+                hosts,
+                reused,
+                evicted,
+                ..
+            } = &mut *state;
+            let entry = hosts.entry(key.clone()).or_default();
+            while let Some(idle) = entry.idle.pop() {
+                if !is_alive(&idle.stream) {
+                    *evicted += 1;
+                    continue;
+                }
+                entry.active += 1;
+                *reused += 1;
+                return Ok(Pooled::new(self, key, idle.stream, true));
+            }
+            if entry.active < self.config.max_per_host {
+                entry.active += 1;
+                break;
+            }
+            state = match deadline {
+                None => self
+                    .returned
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Err(ClientError::Timeout);
+                    }
+                    let (state, _) = self
+                        .returned
+                        .wait_timeout(state, left)
+                        .unwrap_or_else(PoisonError::into_inner);
+                    state
+                }
+            };
+        }
+        state.opened += 1;
+        // the slot is reserved, so the connection is made without the lock
+        drop(state);
+        match connect() {
+            Ok(stream) => Ok(Pooled::new(self, key, stream, false)),
+            Err(err) => {
+                self.release(&key, None);
+                Err(err)
+            }
+        }
+    }
+
+    // gives a connection's slot back, keeping `idle` for reuse if given
+    fn release(&self, key: &str, idle: Option<Idle>) {
+        let mut state = self.lock();
+        if let Some(host) = state.hosts.get_mut(key) {
+            host.active -= 1;
+            host.idle.extend(idle);
+            if host.active == 0 && host.idle.is_empty() {
+                state.hosts.remove(key);
+            }
+        }
+        // waiters for every host share the condvar, so waking just one could
+        // pick a waiter for another host that is still at its cap
+        self.returned.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl State {
+    fn evict(&mut self, now: Instant) {
+        let evicted = &mut self.evicted;
+        self.hosts.retain(|_, host| {
+            let before = host.idle.len();
+            host.idle.retain(|idle| idle.expires > now);
+            *evicted += (before - host.idle.len()) as u64;
+            host.active > 0 || !host.idle.is_empty()
+        });
+    }
+}
+
+// A server that closed an idle connection has sent its FIN by now, and one
+// that sent anything else while idle cannot be trusted with a request.
+fn is_alive(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let alive = matches!(
+        stream.peek(&mut [0]),
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock
+    );
+    stream.set_nonblocking(false).is_ok() && alive
+}
+
+/// A connection checked out of a [`Pool`]. Dropping it closes the
+/// connection; [`Pooled::keep`] returns it for reuse instead.
+#[derive(Debug)]
+pub(crate) struct Pooled<'a> {
+    pool: &'a Pool,
+    key: String,
+    stream: Option<TcpStream>,
+    reused: bool,
+}
+
+impl<'a> Pooled<'a> {
+    fn new(pool: &'a Pool, key: String, stream: TcpStream, reused: bool) -> Self {
+        Self {
+            pool,
+            key,
+            stream: Some(stream),
+            reused,
+        }
+    }
+
+    pub(crate) fn stream(&self) -> &TcpStream {
+        self.stream
+            .as_ref()
+            .expect("stream is present until dropped")
+    }
+
+    /// Whether the connection carried an earlier request, so the server may
+    /// have closed it just as this one was sent.
+    pub(crate) fn reused(&self) -> bool {
+        self.reused
+    }
+
+    /// Returns the connection to the pool after a response that allows it,
+    /// for as long as the response's `Keep-Alive` header and the pool's idle
+    /// timeout both allow.
+    pub(crate) fn keep(mut self, headers: &HeaderMap) {
+        let mut timeout = self.pool.config.idle_timeout;
+        if let Some(server) = keep_alive_timeout(headers) {
+            timeout = timeout.min(server);
+        }
+        let stream = self.stream.take();
+        let idle = stream.filter(|_| !timeout.is_zero()).map(|stream| Idle {
+            stream,
+            expires: Instant::now() + timeout,
+        });
+        self.pool.release(&self.key, idle);
+    }
+}
+
+impl Drop for Pooled<'_> {
+    fn drop(&mut self) {
+        if self.stream.take().is_some() {
+            self.pool.release(&self.key, None);
+        }
+    }
+}
+
+// the `timeout` parameter of `Keep-Alive: timeout=5, max=100`
+fn keep_alive_timeout(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get_all("Keep-Alive")
+        .flat_map(|value| value.split(','))
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("timeout"))
+        .and_then(|(_, secs)| secs.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn connector(listener: &TcpListener) -> impl Fn() -> Result<TcpStream, ClientError> {
+        let addr = listener.local_addr().unwrap();
+        move || TcpStream::connect(addr).map_err(ClientError::connect)
+    }
+
+    #[test]
+    fn test_keep_alive_timeout() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(keep_alive_timeout(&headers), None);
+        headers
+            .insert("Keep-Alive", "max=100, Timeout = 5")
+            .unwrap();
+        assert_eq!(keep_alive_timeout(&headers), Some(Duration::from_secs(5)));
+        headers.insert("Keep-Alive", "timeout=soon").unwrap();
+        assert_eq!(keep_alive_timeout(&headers), None);
+    }
+
+    #[test]
+    fn test_checkout_reuses_and_evicts() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let connect = connector(&listener);
+        let pool = Pool::new(PoolConfig {
+            idle_timeout: Duration::from_millis(50),
+            ..PoolConfig::default()
+        });
+
+        let conn = pool.checkout("Host:1", &connect).unwrap();
+        assert!(!conn.reused());
+        let (_server_side, _) = listener.accept().unwrap();
+        conn.keep(&HeaderMap::new());
+        assert_eq!(pool.stats().idle, 1);
+
+        let conn = pool.checkout("host:1", &connect).unwrap();
+        assert!(conn.reused());
+        assert_eq!(pool.stats().active, 1);
+        conn.keep(&HeaderMap::new());
+
+        // expired while idle
+        std::thread::sleep(Duration::from_millis(80));
+        let conn = pool.checkout("host:1", &connect).unwrap();
+        assert!(!conn.reused());
+        drop(conn);
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                active: 0,
+                idle: 0,
+                opened: 2,
+                reused: 1,
+                evicted: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_closed_idle_connections_are_not_reused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let connect = connector(&listener);
+        let pool = Pool::default();
+
+        let conn = pool.checkout("h", &connect).unwrap();
+        let (server_side, _) = listener.accept().unwrap();
+        conn.keep(&HeaderMap::new());
+        drop(server_side);
+        // give the FIN time to arrive
+        std::thread::sleep(Duration::from_millis(20));
+
+        let conn = pool.checkout("h", &connect).unwrap();
+        assert!(!conn.reused());
+        assert_eq!(pool.stats().evicted, 1);
+
+        // a zero Keep-Alive timeout means the server will not wait for us
+        let mut headers = HeaderMap::new();
+        headers.insert("Keep-Alive", "timeout=0").unwrap();
+        conn.keep(&headers);
+        assert_eq!(pool.stats().idle, 0);
+    }
+
+    #[test]
+    fn test_connections_per_host_are_capped() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let connect = connector(&listener);
+        let pool = Pool::new(PoolConfig {
+            max_per_host: 1,
+            wait_timeout: Some(Duration::from_millis(200)),
+            ..PoolConfig::default()
+        });
+
+        let first = pool.checkout("a", &connect).unwrap();
+        let err = pool.checkout("a", &connect).unwrap_err();
+        assert!(matches!(err, ClientError::Timeout));
+        // other hosts have caps of their own
+        let other = pool.checkout("b", &connect).unwrap();
+        assert_eq!(pool.stats().active, 2);
+
+        // a waiter gets the connection as soon as it is given back
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| pool.checkout("a", &connect).map(|c| c.reused()));
+            std::thread::sleep(Duration::from_millis(10));
+            first.keep(&HeaderMap::new());
+            assert!(waiter.join().unwrap().unwrap());
+        });
+        drop(other);
+        assert_eq!(pool.stats().active, 0);
+        assert_eq!(pool.stats().opened, 2);
+    }
+
+    #[test]
+    fn test_returning_a_connection_wakes_a_waiter_for_its_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let connect = connector(&listener);
+        let pool = Pool::new(PoolConfig {
+            max_per_host: 1,
+            wait_timeout: Some(Duration::from_secs(2)),
+            ..PoolConfig::default()
+        });
+
+        let a = pool.checkout("a", &connect).unwrap();
+        let b = pool.checkout("b", &connect).unwrap();
+        std::thread::scope(|s| {
+            let waiter_a = s.spawn(|| pool.checkout("a", &connect).map(|c| c.reused()));
+            let waiter_b = s.spawn(|| pool.checkout("b", &connect).map(|c| c.reused()));
+            std::thread::sleep(Duration::from_millis(20));
+            // whichever waiter is woken first, b's must not sleep through this
+            // until its wait times out
+            let returned = Instant::now();
+            b.keep(&HeaderMap::new());
+            assert!(waiter_b.join().unwrap().unwrap());
+            assert!(returned.elapsed() < Duration::from_secs(1));
+            a.keep(&HeaderMap::new());
+            assert!(waiter_a.join().unwrap().unwrap());
+        });
+        assert_eq!(pool.stats().opened, 2);
+    }
+}
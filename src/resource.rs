@@ -0,0 +1,586 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::ParseError;
+
+/// The request-target in its four RFC 9112 forms (section 3.2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resource {
+    /// origin-form, e.g. `/search?q=a&page=2`
+    Path { path: String, query: Option<String> },
+    /// absolute-form, sent to proxies, e.g. `http://example.com/search?q=a`
+    Absolute {
+        scheme: String,
+        authority: String,
+        path: String,
+        query: Option<String>,
+    },
+    /// authority-form, only used with CONNECT, e.g. `example.com:443`
+    Authority(String),
+    /// asterisk-form, only used with a server-wide OPTIONS
+    Asterisk,
+}
+
+/// Where the parts of a validated request-target lie, so that it can be
+/// sliced without being copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Form {
+    Origin,
+    /// `path_start` is where the path (or query) follows the authority.
+    Absolute {
+        scheme_end: usize,
+        path_start: usize,
+    },
+    Authority,
+    Asterisk,
+}
+
+impl Form {
+    pub(crate) fn classify(s: &str, offset: usize) -> Result<Form, ParseError> {
+        if let Some(at) = s.bytes().position(|b| !is_target_byte(b)) {
+            return Err(ParseError::InvalidTarget {
+                offset: offset + at,
+            });
+        }
+
+        if s == "*" {
+            return Ok(Form::Asterisk);
+        }
+        if s.starts_with('/') {
+            return Ok(Form::Origin);
+        }
+        if let Some((scheme, rest)) = s.split_once("://") {
+            let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'));
+            if !valid_scheme {
+                return Err(ParseError::InvalidTarget { offset });
+            }
+            let authority_start = scheme.len() + 3;
+            let end = rest.find(['/', '?']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(ParseError::InvalidTarget {
+                    offset: offset + authority_start,
+                });
+            }
+            return Ok(Form::Absolute {
+                scheme_end: scheme.len(),
+                path_start: authority_start + end,
+            });
+        }
+        match s.rsplit_once(':') {
+            Some((host, port))
+                if !host.is_empty()
+                    && !host.contains(['/', '?', '@'])
+                    && !port.is_empty()
+                    && port.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                Ok(Form::Authority)
+            }
+            _ => Err(ParseError::InvalidTarget { offset }),
+        }
+    }
+
+    /// The raw path and query of `s`, which must have been classified as `self`;
+    /// see [`Resource::path`].
+    pub(crate) fn split(self, s: &str) -> (&str, Option<&str>) {
+        match self {
+            Form::Origin => split_query(s),
+            Form::Absolute { path_start, .. } => match split_query(&s[path_start..]) {
+                ("", query) => ("/", query),
+                parts => parts,
+            },
+            Form::Authority => ("", None),
+            Form::Asterisk => ("*", None),
+        }
+    }
+}
+
+impl Resource {
+    pub(crate) fn parse(s: &str, offset: usize) -> Result<Self, ParseError> {
+        Ok(Resource::from_form(s, Form::classify(s, offset)?))
+    }
+
+    /// Builds the resource for `s`, which must have been classified as `form`.
+    pub(crate) fn from_form(s: &str, form: Form) -> Self {
+        let owned =
+            |(path, query): (&str, Option<&str>)| (path.to_string(), query.map(str::to_string));
+        match form {
+            Form::Origin => {
+                let (path, query) = owned(split_query(s));
+                Resource::Path { path, query }
+            }
+            Form::Absolute {
+                scheme_end,
+                path_start,
+            } => {
+                let (path, query) = owned(split_query(&s[path_start..]));
+                Resource::Absolute {
+                    scheme: s[..scheme_end].to_ascii_lowercase(),
+                    authority: s[scheme_end + 3..path_start].to_string(),
+                    path,
+                    query,
+                }
+            }
+            Form::Authority => Resource::Authority(s.to_string()),
+            Form::Asterisk => Resource::Asterisk,
+        }
+    }
+
+    /// The raw path component, still percent-encoded as it arrived on the wire;
+    /// empty for authority-form and `*` for asterisk-form.
+    pub fn path(&self) -> &str {
+        match self {
+            Resource::Path { path, .. } => path,
+            Resource::Absolute { path, .. } if path.is_empty() => "/",
+            Resource::Absolute { path, .. } => path,
+            Resource::Authority(_) => "",
+            Resource::Asterisk => "*",
+        }
+    }
+
+    /// The raw query string, without the leading `?`.
+    pub fn query(&self) -> Option<&str> {
+        match self {
+            Resource::Path { query, .. } | Resource::Absolute { query, .. } => query.as_deref(),
+            Resource::Authority(_) | Resource::Asterisk => None,
+        }
+    }
+
+    /// The host and optional port the target names, if it carries one.
+    pub fn authority(&self) -> Option<&str> {
+        match self {
+            Resource::Absolute { authority, .. } | Resource::Authority(authority) => {
+                Some(authority)
+            }
+            Resource::Path { .. } | Resource::Asterisk => None,
+        }
+    }
+
+    /// Path segments without the leading slash; `/a/b/` yields `a`, `b`, ``.
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        let path = self.path().strip_prefix('/').unwrap_or("");
+        path.split('/').filter(move |_| !path.is_empty())
+    }
+
+    /// The percent-decoded path with `.` and `..` segments resolved, safe to map
+    /// onto a filesystem: `..` never climbs above the root, and an encoded
+    /// backslash separates segments as it would on Windows.
+    pub fn normalized_path(&self) -> Result<String, ParseError> {
+        self.normalized_path_with(DecodeOptions::default())
+    }
+
+    pub fn normalized_path_with(&self, options: DecodeOptions) -> Result<String, ParseError> {
+        let raw = self.path();
+        let Some(rest) = raw.strip_prefix('/') else {
+            return Ok(raw.to_string());
+        };
+
+        let mut segments: Vec<String> = Vec::new();
+        let mut offset = 1;
+        let mut trailing_slash = false;
+        for raw_segment in rest.split('/') {
+            let decoded = decode_segment(raw_segment, offset, options)?;
+            // Windows treats a backslash as a separator, so `..%5C` must not
+            // slip past dot-segment resolution
+            for segment in decoded.split('\\') {
+                trailing_slash = true;
+                match segment {
+                    "." => {}
+                    ".." => {
+                        segments.pop();
+                    }
+                    _ => {
+                        trailing_slash = segment.is_empty();
+                        if !segment.is_empty() {
+                            segments.push(segment.to_string());
+                        }
+                    }
+                }
+            }
+            offset += raw_segment.len() + 1;
+        }
+
+        let mut normalized = String::with_capacity(raw.len());
+        for segment in &segments {
+            normalized.push('/');
+            normalized.push_str(segment);
+        }
+        if trailing_slash || normalized.is_empty() {
+            normalized.push('/');
+        }
+        Ok(normalized)
+    }
+
+    /// The query split into percent-decoded names and values, with `+`
+    /// standing for a space as in HTML forms. An invalid escape, or one that
+    /// does not decode to UTF-8, is refused with an offset into the query.
+    pub fn query_params(&self) -> Result<QueryParams, ParseError> {
+        let mut pairs = Vec::new();
+        let mut offset = 0;
+        for pair in self.query().unwrap_or("").split('&') {
+            if !pair.is_empty() {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                pairs.push((
+                    decode_query_part(name, offset)?,
+                    decode_query_part(value, offset + name.len() + 1)?,
+                ));
+            }
+            offset += pair.len() + 1;
+        }
+        Ok(QueryParams { pairs })
+    }
+}
+
+impl FromStr for Resource {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Resource::parse(s, 0)
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resource::Path { path, .. } => f.write_str(path)?,
+            Resource::Absolute {
+                scheme,
+                authority,
+                path,
+                ..
+            } => write!(f, "{}://{}{}", scheme, authority, path)?,
+            Resource::Authority(authority) => return f.write_str(authority),
+            Resource::Asterisk => return f.write_str("*"),
+        }
+        match self.query() {
+            Some(query) => write!(f, "?{}", query),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Query parameters in the order they appeared; a name may occur more than once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryParams {
+    pairs: Vec<(String, String)>,
+}
+
+impl QueryParams {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.pairs
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+/// Which escapes `Resource::normalized_path_with` refuses outright.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Reject `%2F`; when allowed it is kept encoded so it cannot act as a
+    /// separator, and `%25` stays encoded too so the two remain distinct.
+    pub reject_encoded_slash: bool,
+    /// Reject `%00`, which truncates paths handed to C APIs.
+    pub reject_nul: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            reject_encoded_slash: true,
+            reject_nul: true,
+        }
+    }
+}
+
+// decodes one path segment; `offset` is where the segment starts in the target
+fn decode_segment(raw: &str, offset: usize, options: DecodeOptions) -> Result<String, ParseError> {
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let byte = escaped_byte(bytes, i)
+            .ok_or(ParseError::InvalidPercentEncoding { offset: offset + i })?;
+        match byte {
+            b'/' if options.reject_encoded_slash => {
+                return Err(ParseError::EncodedSlash { offset: offset + i });
+            }
+            b'/' => decoded.extend_from_slice(b"%2F"),
+            // otherwise `%252F` would come out the same as `%2F`
+            b'%' if !options.reject_encoded_slash => decoded.extend_from_slice(b"%25"),
+            0 if options.reject_nul => {
+                return Err(ParseError::EncodedNul { offset: offset + i });
+            }
+            _ => decoded.push(byte),
+        }
+        i += 3;
+    }
+    String::from_utf8(decoded).map_err(|_| ParseError::InvalidPercentEncoding { offset })
+}
+
+// decodes a query name or value; `offset` is where it starts in the query
+fn decode_query_part(raw: &str, offset: usize) -> Result<String, ParseError> {
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let byte = escaped_byte(bytes, i)
+                    .ok_or(ParseError::InvalidPercentEncoding { offset: offset + i })?;
+                decoded.push(byte);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| ParseError::InvalidPercentEncoding { offset })
+}
+
+// the byte written as `%XX` at `i`; the radix parser alone would also take `%+1`
+fn escaped_byte(bytes: &[u8], i: usize) -> Option<u8> {
+    bytes
+        .get(i + 1..i + 3)
+        .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+        .and_then(|hex| std::str::from_utf8(hex).ok())
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+}
+
+fn split_query(s: &str) -> (&str, Option<&str>) {
+    match s.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (s, None),
+    }
+}
+
+// URI characters allowed in a request-target; `#` is excluded because
+// fragments are never sent on the wire.
+fn is_target_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric()
+        || matches!(
+            b,
+            b'-' | b'.'
+                | b'_'
+                | b'~'
+                | b'!'
+                | b'$'
+                | b'&'
+                | b'\''
+                | b'('
+                | b')'
+                | b'*'
+                | b'+'
+                | b','
+                | b';'
+                | b'='
+                | b':'
+                | b'@'
+                | b'/'
+                | b'?'
+                | b'%'
+                | b'['
+                | b']'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_form() {
+        let r: Resource = "/search?q=a&page=2&q=b".parse().unwrap();
+        assert_eq!(r.path(), "/search");
+        assert_eq!(r.query(), Some("q=a&page=2&q=b"));
+        assert_eq!(r.authority(), None);
+
+        let params = r.query_params().unwrap();
+        assert_eq!(params.len(), 3);
+        assert_eq!(params.get("q"), Some("a"));
+        assert_eq!(params.get_all("q").collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(params.get("page"), Some("2"));
+        assert_eq!(params.get("missing"), None);
+        assert_eq!(r.to_string(), "/search?q=a&page=2&q=b");
+    }
+
+    #[test]
+    fn test_query_params_are_decoded() {
+        let r: Resource = "/s?q=a%20b&x=1+2&tom%26jerry=a%3Db&caf%C3%A9=%E2%9C%93&flag"
+            .parse()
+            .unwrap();
+        let params = r.query_params().unwrap();
+        assert_eq!(params.get("q"), Some("a b"));
+        assert_eq!(params.get("x"), Some("1 2"));
+        // encoded separators are data, not separators
+        assert_eq!(params.get("tom&jerry"), Some("a=b"));
+        assert_eq!(params.get("café"), Some("✓"));
+        assert_eq!(params.get("flag"), Some(""));
+        assert_eq!(params.len(), 5);
+
+        // offsets count from the start of the query
+        let bad = |target: &str| target.parse::<Resource>().unwrap().query_params();
+        assert_eq!(
+            bad("/s?a=1&b=%4"),
+            Err(ParseError::InvalidPercentEncoding { offset: 6 })
+        );
+        assert_eq!(
+            bad("/s?a=%+1"),
+            Err(ParseError::InvalidPercentEncoding { offset: 2 })
+        );
+        assert_eq!(
+            bad("/s?a=1&%FF=x"),
+            Err(ParseError::InvalidPercentEncoding { offset: 4 })
+        );
+    }
+
+    #[test]
+    fn test_segments() {
+        let r: Resource = "/api/v1/users/".parse().unwrap();
+        assert_eq!(
+            r.segments().collect::<Vec<_>>(),
+            vec!["api", "v1", "users", ""]
+        );
+
+        let r: Resource = "/".parse().unwrap();
+        assert_eq!(r.segments().count(), 0);
+    }
+
+    #[test]
+    fn test_absolute_form() {
+        let r: Resource = "http://example.com:8080/index.html?x=1".parse().unwrap();
+        assert_eq!(
+            r,
+            Resource::Absolute {
+                scheme: "http".to_string(),
+                authority: "example.com:8080".to_string(),
+                path: "/index.html".to_string(),
+                query: Some("x=1".to_string()),
+            }
+        );
+        assert_eq!(r.authority(), Some("example.com:8080"));
+        assert_eq!(r.to_string(), "http://example.com:8080/index.html?x=1");
+
+        let r: Resource = "http://example.com".parse().unwrap();
+        assert_eq!(r.path(), "/");
+    }
+
+    #[test]
+    fn test_authority_and_asterisk_forms() {
+        let r: Resource = "example.com:443".parse().unwrap();
+        assert_eq!(r, Resource::Authority("example.com:443".to_string()));
+        assert_eq!(r.authority(), Some("example.com:443"));
+
+        let r: Resource = "*".parse().unwrap();
+        assert_eq!(r, Resource::Asterisk);
+        assert_eq!(r.to_string(), "*");
+    }
+
+    #[test]
+    fn test_invalid_targets() {
+        assert_eq!(
+            "/page#section".parse::<Resource>(),
+            Err(ParseError::InvalidTarget { offset: 5 })
+        );
+        assert_eq!(
+            "/a b".parse::<Resource>(),
+            Err(ParseError::InvalidTarget { offset: 2 })
+        );
+        assert!("example.com".parse::<Resource>().is_err());
+        assert!("1http://x/".parse::<Resource>().is_err());
+        assert!("http:///path".parse::<Resource>().is_err());
+    }
+
+    #[test]
+    fn test_normalized_path_decodes_escapes() {
+        let r: Resource = "/my%20file.txt".parse().unwrap();
+        assert_eq!(r.normalized_path().unwrap(), "/my file.txt");
+        assert_eq!(r.path(), "/my%20file.txt");
+
+        let r: Resource = "/caf%C3%A9/".parse().unwrap();
+        assert_eq!(r.normalized_path().unwrap(), "/café/");
+    }
+
+    #[test]
+    fn test_normalized_path_resolves_dot_segments() {
+        let cases = [
+            ("/", "/"),
+            ("/a/./b", "/a/b"),
+            ("/a/b/..", "/a/"),
+            ("/a//b", "/a/b"),
+            ("/../../etc/passwd", "/etc/passwd"),
+            ("/static/%2e%2e/%2E%2E/secret", "/secret"),
+            ("/a/b/../../..", "/"),
+            ("/static/..%5C..%5Csecret", "/secret"),
+            ("/a%5cb%5C", "/a/b/"),
+        ];
+        for (raw, expected) in cases {
+            let r: Resource = raw.parse().unwrap();
+            assert_eq!(r.normalized_path().unwrap(), expected, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_normalized_path_rejections() {
+        let r: Resource = "/a%2Fb".parse().unwrap();
+        assert_eq!(
+            r.normalized_path(),
+            Err(ParseError::EncodedSlash { offset: 2 })
+        );
+        let lenient = DecodeOptions {
+            reject_encoded_slash: false,
+            reject_nul: false,
+        };
+        assert_eq!(r.normalized_path_with(lenient).unwrap(), "/a%2Fb");
+        let r: Resource = "/a%252Fb".parse().unwrap();
+        assert_eq!(r.normalized_path_with(lenient).unwrap(), "/a%252Fb");
+        assert_eq!(r.normalized_path().unwrap(), "/a%2Fb");
+
+        let r: Resource = "/file%00.txt".parse().unwrap();
+        assert_eq!(
+            r.normalized_path(),
+            Err(ParseError::EncodedNul { offset: 5 })
+        );
+
+        let r: Resource = "/bad%zz".parse().unwrap();
+        assert_eq!(
+            r.normalized_path(),
+            Err(ParseError::InvalidPercentEncoding { offset: 4 })
+        );
+
+        let r: Resource = "/%ff".parse().unwrap();
+        assert!(r.normalized_path().is_err());
+    }
+}
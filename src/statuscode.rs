@@ -0,0 +1,230 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::InvalidStatusCode;
+
+/// An HTTP status code (RFC 9110, section 15).
+///
+/// Any three-digit code from 100 to 599 is accepted, registered or not;
+/// [`StatusCode::canonical_reason`] knows the phrases of the IANA registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StatusCode(u16);
+
+macro_rules! status_codes {
+    ($($name:ident = $code:literal, $reason:literal;)+) => {
+        impl StatusCode {
+            $(
+                #[doc = concat!("`", $code, " ", $reason, "`")]
+                pub const $name: StatusCode = StatusCode($code);
+            )+
+
+            /// The reason phrase registered for this code, if any.
+            pub fn canonical_reason(self) -> Option<&'static str> {
+                match self.0 {
+                    $($code => Some($reason),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    CONTINUE = 100, "Continue";
+    SWITCHING_PROTOCOLS = 101, "Switching Protocols";
+    PROCESSING = 102, "Processing";
+    EARLY_HINTS = 103, "Early Hints";
+
+    OK = 200, "OK";
+    CREATED = 201, "Created";
+    ACCEPTED = 202, "Accepted";
+    NON_AUTHORITATIVE_INFORMATION = 203, "Non-Authoritative Information";
+    NO_CONTENT = 204, "No Content";
+    RESET_CONTENT = 205, "Reset Content";
+    PARTIAL_CONTENT = 206, "Partial Content";
+    MULTI_STATUS = 207, "Multi-Status";
+    ALREADY_REPORTED = 208, "Already Reported";
+    IM_USED = 226, "IM Used";
+
+    MULTIPLE_CHOICES = 300, "Multiple Choices";
+    MOVED_PERMANENTLY = 301, "Moved Permanently";
+    FOUND = 302, "Found";
+    SEE_OTHER = 303, "See Other";
+    NOT_MODIFIED = 304, "Not Modified";
+    USE_PROXY = 305, "Use Proxy";
+    TEMPORARY_REDIRECT = 307, "Temporary Redirect";
+    PERMANENT_REDIRECT = 308, "Permanent Redirect";
+
+    BAD_REQUEST = 400, "Bad Request";
+    UNAUTHORIZED = 401, "Unauthorized";
+    PAYMENT_REQUIRED = 402, "Payment Required";
+    FORBIDDEN = 403, "Forbidden";
+    NOT_FOUND = 404, "Not Found";
+    METHOD_NOT_ALLOWED = 405, "Method Not Allowed";
+    NOT_ACCEPTABLE = 406, "Not Acceptable";
+    PROXY_AUTHENTICATION_REQUIRED = 407, "Proxy Authentication Required";
+    REQUEST_TIMEOUT = 408, "Request Timeout";
+    CONFLICT = 409, "Conflict";
+    GONE = 410, "Gone";
+    LENGTH_REQUIRED = 411, "Length Required";
+    PRECONDITION_FAILED = 412, "Precondition Failed";
+    CONTENT_TOO_LARGE = 413, "Content Too Large";
+    URI_TOO_LONG = 414, "URI Too Long";
+    UNSUPPORTED_MEDIA_TYPE = 415, "Unsupported Media Type";
+    RANGE_NOT_SATISFIABLE = 416, "Range Not Satisfiable";
+    EXPECTATION_FAILED = 417, "Expectation Failed";
+    MISDIRECTED_REQUEST = 421, "Misdirected Request";
+    UNPROCESSABLE_CONTENT = 422, "Unprocessable Content";
+    LOCKED = 423, "Locked";
+    FAILED_DEPENDENCY = 424, "Failed Dependency";
+    TOO_EARLY = 425, "Too Early";
+    UPGRADE_REQUIRED = 426, "Upgrade Required";
+    PRECONDITION_REQUIRED = 428, "Precondition Required";
+    TOO_MANY_REQUESTS = 429, "Too Many Requests";
+    REQUEST_HEADER_FIELDS_TOO_LARGE = 431, "Request Header Fields Too Large";
+    UNAVAILABLE_FOR_LEGAL_REASONS = 451, "Unavailable For Legal Reasons";
+
+    INTERNAL_SERVER_ERROR = 500, "Internal Server Error";
+    NOT_IMPLEMENTED = 501, "Not Implemented";
+    BAD_GATEWAY = 502, "Bad Gateway";
+    SERVICE_UNAVAILABLE = 503, "Service Unavailable";
+    GATEWAY_TIMEOUT = 504, "Gateway Timeout";
+    HTTP_VERSION_NOT_SUPPORTED = 505, "HTTP Version Not Supported";
+    VARIANT_ALSO_NEGOTIATES = 506, "Variant Also Negotiates";
+    INSUFFICIENT_STORAGE = 507, "Insufficient Storage";
+    LOOP_DETECTED = 508, "Loop Detected";
+    NOT_EXTENDED = 510, "Not Extended";
+    NETWORK_AUTHENTICATION_REQUIRED = 511, "Network Authentication Required";
+}
+
+impl StatusCode {
+    pub fn from_u16(code: u16) -> Result<Self, InvalidStatusCode> {
+        if (100..=599).contains(&code) {
+            Ok(StatusCode(code))
+        } else {
+            Err(InvalidStatusCode(code.to_string()))
+        }
+    }
+
+    pub fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    /// 1xx: the request was received and processing continues.
+    pub fn is_informational(self) -> bool {
+        self.0 / 100 == 1
+    }
+
+    /// 2xx
+    pub fn is_success(self) -> bool {
+        self.0 / 100 == 2
+    }
+
+    /// 3xx
+    pub fn is_redirection(self) -> bool {
+        self.0 / 100 == 3
+    }
+
+    /// 4xx
+    pub fn is_client_error(self) -> bool {
+        self.0 / 100 == 4
+    }
+
+    /// 5xx
+    pub fn is_server_error(self) -> bool {
+        self.0 / 100 == 5
+    }
+}
+
+impl Default for StatusCode {
+    fn default() -> Self {
+        StatusCode::OK
+    }
+}
+
+impl TryFrom<u16> for StatusCode {
+    type Error = InvalidStatusCode;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        StatusCode::from_u16(code)
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(status: StatusCode) -> Self {
+        status.0
+    }
+}
+
+impl FromStr for StatusCode {
+    type Err = InvalidStatusCode;
+
+    // exactly three digits, as in a status line
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 3 || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(InvalidStatusCode(s.to_string()));
+        }
+        s.parse()
+            .map_err(|_| InvalidStatusCode(s.to_string()))
+            .and_then(StatusCode::from_u16)
+    }
+}
+
+/// Writes the code followed by its canonical reason, e.g. `404 Not Found`.
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.canonical_reason() {
+            Some(reason) => write!(f, "{} {}", self.0, reason),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_reasons() {
+        assert_eq!(StatusCode::OK.canonical_reason(), Some("OK"));
+        assert_eq!(
+            StatusCode::NO_CONTENT.canonical_reason(),
+            Some("No Content")
+        );
+        assert_eq!(
+            StatusCode::from_u16(503).unwrap().canonical_reason(),
+            Some("Service Unavailable")
+        );
+        assert_eq!(StatusCode::from_u16(299).unwrap().canonical_reason(), None);
+        assert_eq!(
+            StatusCode::MOVED_PERMANENTLY.to_string(),
+            "301 Moved Permanently"
+        );
+        assert_eq!(StatusCode::from_u16(599).unwrap().to_string(), "599");
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(StatusCode::CONTINUE.is_informational());
+        assert!(StatusCode::CREATED.is_success());
+        assert!(StatusCode::PERMANENT_REDIRECT.is_redirection());
+        assert!(StatusCode::UNAUTHORIZED.is_client_error());
+        assert!(StatusCode::BAD_GATEWAY.is_server_error());
+        assert!(!StatusCode::NOT_FOUND.is_success());
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(StatusCode::try_from(204), Ok(StatusCode::NO_CONTENT));
+        assert_eq!(u16::from(StatusCode::NOT_FOUND), 404);
+        assert_eq!(
+            StatusCode::from_u16(99),
+            Err(InvalidStatusCode("99".to_string()))
+        );
+        assert!(StatusCode::from_u16(600).is_err());
+        assert_eq!("418".parse(), Ok(StatusCode::from_u16(418).unwrap()));
+        assert!("+20".parse::<StatusCode>().is_err());
+        assert!("2000".parse::<StatusCode>().is_err());
+        assert!("".parse::<StatusCode>().is_err());
+    }
+}